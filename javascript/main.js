@@ -6,6 +6,14 @@ function console_error(...args) {
     Game.notify(args.join(' '));
 }
 
+// console entry point, e.g. `command("help")`
+global.command = function(input) {
+    if (wasm_module && wasm_module.__wasm) {
+        return wasm_module.command(input);
+    }
+    return "wasm module is not loaded yet";
+}
+
 module.exports.loop = function() {
     try {
         if (wasm_module && wasm_module.__wasm) {
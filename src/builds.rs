@@ -6,22 +6,50 @@ use std::{
 	collections::HashMap,
 	fmt::{Display, Formatter, Result},
 	iter::repeat,
+	str::FromStr,
 };
 
-use crate::structures::CreepParts;
+use crate::{jobs::JobKind, structures::CreepParts};
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub enum CreepBuild {
 	Worker1_1,
 	Worker2_1,
 	Worker2_2,
+	/// Stays at the controller, drawing energy from its container or link
+	Upgrader,
+	/// Builds construction sites, drawing energy stored in the room
+	Builder,
+	/// Mines the room's mineral, emptying into the container next to it
+	MineralMiner,
 }
 
 impl Display for CreepBuild {
 	fn fmt(&self, f: &mut Formatter) -> Result { write!(f, "{:?}", self) }
 }
 
+impl FromStr for CreepBuild {
+	type Err = ();
+
+	fn from_str(name: &str) -> std::result::Result<Self, ()> {
+		CreepBuild::ALL
+			.iter()
+			.find(|build| build.to_string().eq_ignore_ascii_case(name))
+			.cloned()
+			.ok_or(())
+	}
+}
+
 impl CreepBuild {
+	pub const ALL: &'static [CreepBuild] = &[
+		CreepBuild::Worker1_1,
+		CreepBuild::Worker2_1,
+		CreepBuild::Worker2_2,
+		CreepBuild::Upgrader,
+		CreepBuild::Builder,
+		CreepBuild::MineralMiner,
+	];
+
 	pub fn parts(&self) -> CreepParts {
 		CreepParts(
 			match self {
@@ -29,10 +57,33 @@ impl CreepBuild {
 
 				CreepBuild::Worker2_1 => vec![(Part::Move, 2), (Part::Work, 1), (Part::Carry, 2)],
 				CreepBuild::Worker2_2 => vec![(Part::Move, 3), (Part::Work, 2), (Part::Carry, 4)],
+
+				CreepBuild::Upgrader => vec![(Part::Move, 1), (Part::Work, 2), (Part::Carry, 1)],
+				CreepBuild::Builder => vec![(Part::Move, 2), (Part::Work, 1), (Part::Carry, 2)],
+				CreepBuild::MineralMiner => {
+					vec![(Part::Move, 3), (Part::Work, 6), (Part::Carry, 2)]
+				}
 			}
 			.iter()
 			.copied()
 			.collect::<HashMap<_, _>>(),
 		)
 	}
+
+	/// The only job a dedicated build takes. Builds without a role take any job they can do.
+	pub fn role(&self) -> Option<JobKind> {
+		match self {
+			CreepBuild::Upgrader => Some(JobKind::Upgrade),
+			CreepBuild::Builder => Some(JobKind::Build),
+			CreepBuild::MineralMiner => Some(JobKind::Mine),
+			_ => None,
+		}
+	}
+
+	/// Whether a job's offers are kept for the build dedicated to it
+	pub fn dedicated(job: JobKind) -> bool {
+		CreepBuild::ALL
+			.iter()
+			.any(|build| build.role() == Some(job))
+	}
 }
@@ -0,0 +1,478 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// Bits stored in each character of a packed string
+const BITS_PER_CHAR: u32 = 15;
+/// First code point used when packing. Every packed character stays below the surrogate range
+/// so strings survive being stored as UTF-16.
+const CHAR_BASE: u32 = 0x4e00;
+
+/// Longest distance a match can refer back
+const WINDOW: usize = 4096;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = MIN_MATCH + 15;
+const HASH_SIZE: usize = 1 << 12;
+/// Candidates examined per position when looking for a match
+const MAX_CHAIN: usize = 16;
+
+fn hash(bytes: &[u8]) -> usize {
+	((bytes[0] as usize) << 8 ^ (bytes[1] as usize) << 4 ^ bytes[2] as usize) & (HASH_SIZE - 1)
+}
+
+/// LZSS compression. Tokens are grouped in eights behind a flag byte, where a set bit marks a
+/// two byte back reference of 12 bits distance and 4 bits length, and a clear bit a literal.
+pub fn compress(input: &[u8]) -> Vec<u8> {
+	let mut out = Vec::with_capacity(input.len() / 2);
+	let mut head = vec![usize::MAX; HASH_SIZE];
+	let mut prev = vec![usize::MAX; input.len()];
+
+	let mut flag_index = 0;
+	let mut token = 8;
+	let mut pos = 0;
+
+	let insert = |pos: usize, head: &mut Vec<usize>, prev: &mut Vec<usize>| {
+		if pos + MIN_MATCH <= input.len() {
+			let h = hash(&input[pos..]);
+			prev[pos] = head[h];
+			head[h] = pos;
+		}
+	};
+
+	while pos < input.len() {
+		if token == 8 {
+			flag_index = out.len();
+			out.push(0);
+			token = 0;
+		}
+
+		let mut best = (0, 0);
+		if pos + MIN_MATCH <= input.len() {
+			let mut candidate = head[hash(&input[pos..])];
+			let limit = (input.len() - pos).min(MAX_MATCH);
+			for _ in 0..MAX_CHAIN {
+				if candidate == usize::MAX || pos - candidate > WINDOW {
+					break;
+				}
+				let length = (0..limit)
+					.take_while(|&i| input[candidate + i] == input[pos + i])
+					.count();
+				if length > best.1 {
+					best = (pos - candidate, length);
+				}
+				candidate = prev[candidate];
+			}
+		}
+
+		if best.1 >= MIN_MATCH {
+			let (distance, length) = (best.0 - 1, best.1 - MIN_MATCH);
+			out[flag_index] |= 1 << token;
+			out.push((distance >> 4) as u8);
+			out.push(((distance & 0xf) << 4 | length) as u8);
+			for i in pos..pos + best.1 {
+				insert(i, &mut head, &mut prev);
+			}
+			pos += best.1;
+		}
+		else {
+			out.push(input[pos]);
+			insert(pos, &mut head, &mut prev);
+			pos += 1;
+		}
+		token += 1;
+	}
+
+	out
+}
+
+/// Reverse of `compress`, `None` if the input is malformed
+pub fn decompress(input: &[u8]) -> Option<Vec<u8>> {
+	let mut out = Vec::with_capacity(input.len() * 2);
+	let mut bytes = input.iter().copied();
+
+	while let Some(flags) = bytes.next() {
+		for token in 0..8 {
+			if flags & 1 << token == 0 {
+				match bytes.next() {
+					Some(byte) => out.push(byte),
+					None => return Some(out),
+				}
+				continue;
+			}
+
+			let (high, low) = (bytes.next()? as usize, bytes.next()? as usize);
+			let distance = (high << 4 | low >> 4) + 1;
+			let length = (low & 0xf) + MIN_MATCH;
+			if distance > out.len() {
+				return None;
+			}
+			let start = out.len() - distance;
+			for i in 0..length {
+				out.push(out[start + i]);
+			}
+		}
+	}
+
+	Some(out)
+}
+
+/// Pack bytes into a string of 15 bit characters, prefixed with the byte length
+pub fn pack(bytes: &[u8]) -> String {
+	// Every packed character is three bytes of UTF-8, so they are written directly
+	let mut out = Vec::with_capacity((bytes.len() * 8 / BITS_PER_CHAR as usize + 3) * 3);
+	let mask = (1 << BITS_PER_CHAR) - 1;
+	let push = |out: &mut Vec<u8>, value: u32| {
+		let c = CHAR_BASE + (value & mask);
+		out.extend_from_slice(&[
+			0xe0 | (c >> 12) as u8,
+			0x80 | (c >> 6 & 0x3f) as u8,
+			0x80 | (c & 0x3f) as u8,
+		]);
+	};
+
+	let length = bytes.len() as u32;
+	push(&mut out, length >> BITS_PER_CHAR);
+	push(&mut out, length);
+
+	let (mut buffer, mut bits) = (0u32, 0);
+	for byte in bytes {
+		buffer = buffer << 8 | *byte as u32;
+		bits += 8;
+		if bits >= BITS_PER_CHAR {
+			bits -= BITS_PER_CHAR;
+			push(&mut out, buffer >> bits);
+			buffer &= (1 << bits) - 1;
+		}
+	}
+	if bits > 0 {
+		push(&mut out, buffer << (BITS_PER_CHAR - bits));
+	}
+
+	String::from_utf8(out).unwrap()
+}
+
+/// Reverse of `pack`, `None` if the string was not produced by it
+pub fn unpack(packed: &str) -> Option<Vec<u8>> {
+	let range = CHAR_BASE..CHAR_BASE + (1 << BITS_PER_CHAR);
+	let bytes = packed.as_bytes();
+	if bytes.len() % 3 != 0 {
+		return None;
+	}
+	let mut values = bytes.chunks(3).map(|c| {
+		let c = (c[0] as u32 & 0x0f) << 12 | (c[1] as u32 & 0x3f) << 6 | c[2] as u32 & 0x3f;
+		if range.contains(&c) {
+			Some(c - CHAR_BASE)
+		}
+		else {
+			None
+		}
+	});
+
+	let length = (values.next()?? << BITS_PER_CHAR | values.next()??) as usize;
+	let mut out = Vec::with_capacity(length + 1);
+
+	let (mut buffer, mut bits) = (0u32, 0);
+	for value in values {
+		buffer = buffer << BITS_PER_CHAR | value?;
+		bits += BITS_PER_CHAR;
+		while bits >= 8 {
+			bits -= 8;
+			out.push((buffer >> bits) as u8);
+		}
+		buffer &= (1 << bits) - 1;
+	}
+
+	// The final character may carry a padding byte
+	if out.len() == length || out.len() == length + 1 {
+		out.truncate(length);
+		Some(out)
+	}
+	else {
+		None
+	}
+}
+
+/// First byte of binary payloads, followed by `FORMAT_VERSION`. JSON never starts with it, so
+/// payloads identify their own encoding and data written before a change of encoding can still be
+/// read.
+const VERSIONED_MARKER: u8 = 1;
+/// Version of the binary layout. Bincode is not self-describing, so this must be bumped whenever a
+/// persisted type gains a field or has its variants reordered, and older data is then rejected
+/// rather than misread.
+const FORMAT_VERSION: u8 = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Encoding {
+	Json,
+	Binary,
+}
+
+/// Encoding used for each kind of persisted state
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncodingConfig {
+	/// Creep memory, stored as a packed string when binary
+	pub creeps: Encoding,
+	pub intel: Encoding,
+}
+
+impl Default for EncodingConfig {
+	fn default() -> Self {
+		EncodingConfig {
+			creeps: Encoding::Json,
+			intel: Encoding::Binary,
+		}
+	}
+}
+
+pub fn to_bytes<T: Serialize>(value: &T, encoding: Encoding) -> Result<Vec<u8>> {
+	match encoding {
+		Encoding::Json => Ok(serde_json::to_vec(value)?),
+		Encoding::Binary => {
+			let mut bytes = vec![VERSIONED_MARKER, FORMAT_VERSION];
+			bincode::serialize_into(&mut bytes, value)?;
+			Ok(bytes)
+		}
+	}
+}
+
+/// Decode bytes produced by `to_bytes` in either encoding
+pub fn from_bytes<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+	match bytes {
+		[VERSIONED_MARKER, FORMAT_VERSION, rest @ ..] => Ok(bincode::deserialize(rest)?),
+		[VERSIONED_MARKER, version, ..] => Err(Error::Version(*version, FORMAT_VERSION)),
+		_ => Ok(serde_json::from_slice(bytes)?),
+	}
+}
+
+/// Binary encode a value into a string which can be stored in `Memory`
+pub fn to_packed<T: Serialize>(value: &T) -> Result<String> {
+	Ok(pack(&to_bytes(value, Encoding::Binary)?))
+}
+
+pub fn from_packed<T: DeserializeOwned>(packed: &str) -> Result<T> {
+	from_bytes(&unpack(packed).ok_or(Error::Unpack)?)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::{collections::HashMap, time::Instant};
+
+	use crate::{intel::RoomIntel, structures::CreepState};
+
+	/// Deterministic bytes drawn from a small alphabet, so they compress but not trivially
+	fn noise(len: usize, alphabet: u8) -> Vec<u8> {
+		let mut state = 0x2545_f491_u32;
+		(0..len)
+			.map(|_| {
+				state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+				b'a' + (state >> 16) as u8 % alphabet
+			})
+			.collect()
+	}
+
+	fn round_trip(input: &[u8]) {
+		let compressed = compress(input);
+		assert_eq!(decompress(&compressed).as_deref(), Some(input));
+
+		let packed = pack(&compressed);
+		assert!(packed
+			.encode_utf16()
+			.all(|unit| !(0xd800..=0xdfff).contains(&unit)));
+		assert_eq!(unpack(&packed), Some(compressed));
+	}
+
+	#[test]
+	fn empty_input() {
+		assert!(compress(&[]).is_empty());
+		assert_eq!(decompress(&[]), Some(Vec::new()));
+		assert_eq!(unpack(&pack(&[])), Some(Vec::new()));
+		round_trip(&[]);
+	}
+
+	#[test]
+	fn long_runs() {
+		let run = vec![b'x'; 100_000];
+		let compressed = compress(&run);
+		assert!(compressed.len() < run.len() / 5);
+		round_trip(&run);
+	}
+
+	#[test]
+	fn shorter_than_window() {
+		round_trip(b"ab");
+		round_trip(b"abcabcabc");
+		round_trip(&noise(WINDOW / 2, 4));
+	}
+
+	#[test]
+	fn longer_than_window() {
+		round_trip(&noise(WINDOW * 5 + 7, 6));
+
+		// A repeat just out of reach has to be written as literals
+		let block = noise(WINDOW + 1, 26);
+		let mut input = block.clone();
+		input.extend(&block);
+		round_trip(&input);
+	}
+
+	#[test]
+	fn pack_every_length() {
+		let bytes = (0..=255).collect::<Vec<u8>>();
+		for len in 0..bytes.len() {
+			let packed = pack(&bytes[..len]);
+			assert!(packed.chars().all(|c| (c as u32) < 0xd800));
+			assert_eq!(unpack(&packed).as_deref(), Some(&bytes[..len]));
+		}
+	}
+
+	#[test]
+	fn unpack_rejects_foreign_strings() {
+		assert_eq!(unpack("{}"), None);
+		assert_eq!(unpack("abc"), None);
+	}
+
+	#[test]
+	fn decompress_rejects_references_before_start() {
+		assert_eq!(decompress(&[0x01, 0x00, 0x10]), None);
+	}
+
+	const ROUNDS: u32 = 2000;
+
+	/// A creep deep in a job with one suspended, built from JSON since targets are normally
+	/// created from game object ids
+	fn creep_state() -> CreepState {
+		serde_json::from_str(
+			r#"{
+				"build": "Worker2_2",
+				"job": {"Build": {"Building": "5f3c9a1e2b7d4c0012ab34cd"}},
+				"home": "W12N34",
+				"interrupt": "SpawnStarved",
+				"suspended": {"Harvest": {"Harvesting": "5bbcad0e9099fc012e636f1a"}}
+			}"#,
+		)
+		.unwrap()
+	}
+
+	fn intel() -> HashMap<String, RoomIntel> {
+		(0..200)
+			.map(|i| {
+				let intel = RoomIntel {
+					owner: if i % 7 == 0 {
+						Some("someone".to_string())
+					}
+					else {
+						None
+					},
+					reserved_by: None,
+					mine: i % 50 == 0,
+					level: (i % 9) as u8,
+					hostile_creeps: (i % 3) as u16,
+					hostile_towers: (i % 5 == 0) as u16,
+					last_seen: 30_000_000 + i,
+				};
+				(format!("W{}N{}", i % 20, i / 20), intel)
+			})
+			.collect()
+	}
+
+	/// Encoded size and decode time of a value, through the packed binary path and through JSON
+	fn compare<T>(value: &T) -> ((usize, f64), (usize, f64))
+	where
+		T: Serialize + DeserializeOwned,
+	{
+		let time = |f: &dyn Fn()| {
+			let start = Instant::now();
+			(0..ROUNDS).for_each(|_| f());
+			start.elapsed().as_secs_f64() * 1e6 / ROUNDS as f64
+		};
+
+		let packed = to_packed(value).unwrap();
+		let json = serde_json::to_string(value).unwrap();
+		let binary = (
+			packed.encode_utf16().count(),
+			time(&|| drop(from_packed::<T>(&packed).unwrap())),
+		);
+		let json = (
+			json.encode_utf16().count(),
+			time(&|| drop(serde_json::from_str::<T>(&json).unwrap())),
+		);
+		(binary, json)
+	}
+
+	#[test]
+	fn creep_state_round_trip() {
+		let state = creep_state();
+		let packed = to_packed(&state).unwrap();
+		assert_eq!(
+			format!("{:?}", from_packed::<CreepState>(&packed).unwrap()),
+			format!("{:?}", state)
+		);
+
+		let json = to_bytes(&state, Encoding::Json).unwrap();
+		assert_eq!(
+			format!("{:?}", from_bytes::<CreepState>(&json).unwrap()),
+			format!("{:?}", state)
+		);
+	}
+
+	#[test]
+	fn creep_state_without_interrupt_round_trip() {
+		let mut state = creep_state();
+		state.interrupt = None;
+		state.suspended = None;
+		let packed = to_packed(&state).unwrap();
+		assert_eq!(
+			format!("{:?}", from_packed::<CreepState>(&packed).unwrap()),
+			format!("{:?}", state)
+		);
+	}
+
+	#[test]
+	fn intel_round_trip() {
+		let intel = intel();
+		let bytes = to_bytes(&intel, Encoding::Binary).unwrap();
+		let decoded = from_bytes::<HashMap<String, RoomIntel>>(&bytes).unwrap();
+		assert_eq!(decoded.len(), intel.len());
+		intel.iter().for_each(|(room, intel)| {
+			assert_eq!(format!("{:?}", decoded[room]), format!("{:?}", intel));
+		});
+	}
+
+	#[test]
+	fn other_versions_are_rejected() {
+		let mut bytes = to_bytes(&creep_state(), Encoding::Binary).unwrap();
+		bytes[1] = FORMAT_VERSION + 1;
+		assert!(matches!(
+			from_bytes::<CreepState>(&bytes),
+			Err(Error::Version(_, FORMAT_VERSION))
+		));
+
+		// Unversioned payloads from before the marker carried a version
+		let mut unversioned = vec![0];
+		unversioned.extend(bincode::serialize(&creep_state()).unwrap());
+		assert!(from_bytes::<CreepState>(&unversioned).is_err());
+	}
+
+	/// Run with `--nocapture` to see the figures. Memory is parsed every tick, so size stands in
+	/// for the CPU spent parsing it, alongside the measured decode time.
+	#[test]
+	fn binary_is_smaller_than_json() {
+		let ((creep_size, creep_time), (creep_json_size, creep_json_time)) =
+			compare(&creep_state());
+		let ((intel_size, intel_time), (intel_json_size, intel_json_time)) = compare(&intel());
+
+		println!(
+			"creep state: packed {} chars in {:.2}us, json {} chars in {:.2}us",
+			creep_size, creep_time, creep_json_size, creep_json_time
+		);
+		println!(
+			"intel: packed {} chars in {:.2}us, json {} chars in {:.2}us",
+			intel_size, intel_time, intel_json_size, intel_json_time
+		);
+		assert!(creep_size < creep_json_size);
+		assert!(intel_size < intel_json_size);
+	}
+}
@@ -0,0 +1,177 @@
+use screeps::{
+	constants::ResourceType,
+	objects::{Creep, Room, StructureSpawn},
+};
+
+use std::collections::HashMap;
+
+use crate::{
+	builds::CreepBuild,
+	context::TickContext,
+	economy,
+	error::{Error, Result},
+	jobs::{mineable, JobKind},
+	structures::CreepState,
+	util::room_name,
+};
+
+/// Storage energy kept back from upgrading
+const STORAGE_RESERVE: u32 = 20_000;
+/// Storage energy above the reserve which pays for each extra upgrader
+const SURPLUS_PER_UPGRADER: u32 = 25_000;
+/// Remaining construction progress which warrants each builder
+const PROGRESS_PER_BUILDER: u32 = 5_000;
+
+pub struct Colony {
+	pub name: String,
+	pub room: Room,
+	pub spawns: Vec<StructureSpawn>,
+	pub creeps_by_build: HashMap<CreepBuild, u16>,
+	pub creeps_by_job: HashMap<JobKind, u16>,
+}
+
+impl Colony {
+	fn new(room: Room) -> Self {
+		Colony {
+			name: room_name(&room),
+			room,
+			spawns: Vec::new(),
+			creeps_by_build: HashMap::new(),
+			creeps_by_job: HashMap::new(),
+		}
+	}
+
+	/// Build one colony for every owned room, attaching the spawns inside it and counting the
+	/// creeps which call it home
+	pub fn gather(
+		creep_pairs: &[(Creep, CreepState)],
+		rooms: &[Room],
+		spawns: &[StructureSpawn],
+	) -> Vec<Colony> {
+		let mut colonies = rooms
+			.iter()
+			.filter(|room| owned(room))
+			.cloned()
+			.map(Colony::new)
+			.collect::<Vec<_>>();
+
+		spawns.iter().for_each(|spawner| {
+			let room = spawner.room().unwrap();
+			let name = room_name(&room);
+			if let Some(colony) = colonies.iter_mut().find(|c| c.name == name) {
+				colony.spawns.push(spawner.clone());
+			}
+		});
+
+		creep_pairs.iter().for_each(|(_, state)| {
+			if let Some(colony) = colonies.iter_mut().find(|c| c.name == state.home) {
+				colony
+					.creeps_by_build
+					.entry(state.build.clone())
+					.and_modify(|x| *x += 1)
+					.or_insert(1);
+				colony
+					.creeps_by_job
+					.entry(state.job.kind())
+					.and_modify(|x| *x += 1)
+					.or_insert(1);
+			}
+		});
+
+		colonies
+	}
+
+	pub fn level(&self) -> u8 { self.room.controller().map(|c| c.level()).unwrap_or(0) }
+
+	/// Creeps of a dedicated build the colony wants once the recipe is filled, `None` for builds
+	/// spawned through the recipe
+	pub fn dedicated_target(&self, ctx: &TickContext, build: &CreepBuild) -> Option<u16> {
+		match build {
+			CreepBuild::Upgrader => Some(self.upgraders_target()),
+			CreepBuild::Builder => Some(self.builders_target(ctx)),
+			CreepBuild::MineralMiner => Some(mineable(ctx, self).is_some() as u16),
+			_ => None,
+		}
+	}
+
+	/// Builders for the construction left in the room, none once everything is built
+	fn builders_target(&self, ctx: &TickContext) -> u16 {
+		let remaining = ctx
+			.construction_sites_in(&self.name)
+			.iter()
+			.map(|site| site.progress_total().saturating_sub(site.progress()))
+			.sum::<u32>();
+		let wanted = (remaining + PROGRESS_PER_BUILDER - 1) / PROGRESS_PER_BUILDER;
+		(wanted as u16).min(economy::policy(&self.name).max_builders)
+	}
+
+	/// Upgraders kept alive once the recipe is filled, with more while the storage has energy to
+	/// spare, up to what the room's spending policy allows
+	fn upgraders_target(&self) -> u16 {
+		if self.room.controller().is_none() {
+			return 0;
+		}
+		// Upgrading is capped at 15 energy per tick at the top level
+		if self.level() == 8 {
+			return 1;
+		}
+		let stored = self
+			.room
+			.storage()
+			.map(|storage| {
+				storage
+					.store()
+					.get_used_capacity(Some(ResourceType::Energy))
+			})
+			.unwrap_or(0);
+		let surplus = stored.saturating_sub(STORAGE_RESERVE);
+		let wanted = 1 + (surplus / SURPLUS_PER_UPGRADER) as u16;
+		wanted.min(economy::policy(&self.name).max_upgraders)
+	}
+
+	/// Number of creeps from the current spawner recipe this colony tries to keep alive
+	pub fn population_target(&self) -> u16 {
+		match self.level() {
+			0 | 1 => 5,
+			_ => 10,
+		}
+	}
+}
+
+/// Names of the rooms we own, each of which is a colony
+pub fn owned_rooms(rooms: &[Room]) -> Vec<String> {
+	rooms
+		.iter()
+		.filter(|room| owned(room))
+		.map(room_name)
+		.collect()
+}
+
+fn owned(room: &Room) -> bool { room.controller().map(|c| c.my()).unwrap_or(false) }
+
+/// Move a creep to a different colony, which must be one of the `owned` rooms. The creep keeps its
+/// current job and will be counted towards the new colony from the next strategy pass.
+pub fn reassign(
+	creep_pairs: &mut [(Creep, CreepState)],
+	creep_name: &str,
+	home: &str,
+	owned: &[String],
+) -> Result<()> {
+	if !owned.iter().any(|room| room == home) {
+		return Err(Error::Command(format!(
+			"{} is not a colony, expected one of: {}",
+			home,
+			owned.join(", ")
+		)));
+	}
+	match creep_pairs
+		.iter_mut()
+		.find(|(creep, _)| creep.name().as_string().as_deref() == Some(creep_name))
+	{
+		Some((_, state)) => {
+			state.home = home.to_string();
+			Ok(())
+		}
+		None => Err(Error::Command(format!("no creep named {}", creep_name))),
+	}
+}
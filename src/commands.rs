@@ -0,0 +1,424 @@
+use wasm_bindgen::prelude::*;
+
+use js_sys::Object;
+
+use screeps::{
+	constants::{Find, ReturnCode},
+	objects::{ConstructionSite, Creep, Room, StructureSpawn},
+	Game,
+};
+
+use std::collections::HashMap;
+
+use crate::{
+	builds::CreepBuild,
+	colony::{owned_rooms, reassign, Colony},
+	config, economy,
+	error::{Error, Result},
+	intel,
+	jobs::CreepJob,
+	logging::Level,
+	profiler,
+	util::{
+		copy_state_in, copy_state_out, find_creep, room_find, room_name, spawn_creep, visible_room,
+	},
+	visuals::Layers,
+};
+
+struct Command {
+	name: &'static str,
+	usage: &'static str,
+	help: &'static str,
+	run: fn(&[&str]) -> Result<String>,
+}
+
+const COMMANDS: &[Command] = &[
+	Command {
+		name: "help",
+		usage: "help [command]",
+		help: "List commands, or describe one",
+		run: help,
+	},
+	Command {
+		name: "spawn",
+		usage: "spawn <room> <build>",
+		help: "Spawn a creep of the given build from an idle spawn in the room",
+		run: spawn,
+	},
+	Command {
+		name: "job",
+		usage: "job <creep> show|reset",
+		help: "Show a creep's state or reset its job so the strategy reassigns it",
+		run: job,
+	},
+	Command {
+		name: "reassign",
+		usage: "reassign <creep> <room>",
+		help: "Move a creep to another colony",
+		run: reassign_creep,
+	},
+	Command {
+		name: "colony",
+		usage: "colony show <room>",
+		help: "Show a colony's level, population target and creeps",
+		run: colony,
+	},
+	Command {
+		name: "plan",
+		usage: "plan show <room>",
+		help: "List the structures planned in a room",
+		run: plan,
+	},
+	Command {
+		name: "intel",
+		usage: "intel <room>",
+		help: "Show what is known about a room",
+		run: room_intel,
+	},
+	Command {
+		name: "profile",
+		usage: "profile on|off|report [count]",
+		help: "Control the CPU profiler",
+		run: profile,
+	},
+	Command {
+		name: "pixels",
+		usage: "pixels on|off",
+		help: "Toggle pixel generation when the bucket is full",
+		run: pixels,
+	},
+	Command {
+		name: "log",
+		usage: "log level <level> [module] | log room <room>|clear | log creep <creep>|clear",
+		help: "Change log filtering",
+		run: log,
+	},
+	Command {
+		name: "visual",
+		usage: "visual <room> on|off [jobs|sources|planned|costs|threats]",
+		help: "Toggle debug overlays in a room, or a single overlay layer",
+		run: visual,
+	},
+];
+
+fn usage(name: &str) -> Error {
+	let usage = COMMANDS
+		.iter()
+		.find(|command| command.name == name)
+		.map(|command| command.usage)
+		.unwrap_or("");
+	Error::Command(format!("usage: {}", usage))
+}
+
+/// Run a console command, for example `command("spawn W1N1 Worker2_1")`
+#[wasm_bindgen(js_name = command)]
+pub fn command_entry(input: &str) -> String {
+	let args = input.split_whitespace().collect::<Vec<_>>();
+	let name = match args.first() {
+		Some(name) => *name,
+		None => return help(&[]).unwrap(),
+	};
+
+	match COMMANDS.iter().find(|command| command.name == name) {
+		Some(command) => match (command.run)(&args[1..]) {
+			Ok(out) => out,
+			Err(e) => format!("{}: {}", name, e),
+		},
+		None => format!("unknown command {}, try help", name),
+	}
+}
+
+fn help(args: &[&str]) -> Result<String> {
+	match args {
+		[] => Ok(COMMANDS
+			.iter()
+			.map(|command| format!("{:<60} {}", command.usage, command.help))
+			.collect::<Vec<_>>()
+			.join("\n")),
+		[name] => COMMANDS
+			.iter()
+			.find(|command| command.name == *name)
+			.map(|command| format!("{}\n{}", command.usage, command.help))
+			.ok_or_else(|| Error::Command(format!("unknown command {}", name))),
+		_ => Err(usage("help")),
+	}
+}
+
+fn spawn(args: &[&str]) -> Result<String> {
+	let (room, build) = match args {
+		[room, build] => (*room, *build),
+		_ => return Err(usage("spawn")),
+	};
+
+	let build = build.parse::<CreepBuild>().map_err(|_| {
+		let builds = CreepBuild::ALL
+			.iter()
+			.map(|build| build.to_string())
+			.collect::<Vec<_>>();
+		Error::Command(format!("builds are {}", builds.join(", ")))
+	})?;
+
+	let spawner = Object::values(&Game::spawns())
+		.iter()
+		.map(StructureSpawn::from)
+		.filter(|spawner| spawner.room().as_ref().map(room_name).as_deref() == Some(room))
+		.find(|spawner| spawner.spawning().is_none())
+		.ok_or_else(|| Error::Command(format!("no idle spawn in {}", room)))?;
+
+	match spawn_creep(&spawner, &build) {
+		ReturnCode::Ok => Ok(format!(
+			"spawning {} at {}",
+			build,
+			spawner.name().as_string().unwrap()
+		)),
+		x => Err(Error::UnhandledErrorCode(x)),
+	}
+}
+
+fn creep_arg(name: &str) -> Result<Creep> {
+	find_creep(name).ok_or_else(|| Error::Command(format!("no creep named {}", name)))
+}
+
+fn job(args: &[&str]) -> Result<String> {
+	match args {
+		[name, "show"] => {
+			let (_, state) = copy_state_in(creep_arg(name)?);
+			Ok(format!("{} ({}): {:?}", name, state.home, state))
+		}
+		[name, "reset"] => {
+			let (creep, mut state) = copy_state_in(creep_arg(name)?);
+			let previous = state.job.label();
+			state.job = CreepJob::None;
+			state.interrupt = None;
+			state.suspended = None;
+			copy_state_out(&creep, state)?;
+			Ok(format!("{} reset from {}", name, previous))
+		}
+		_ => Err(usage("job")),
+	}
+}
+
+fn reassign_creep(args: &[&str]) -> Result<String> {
+	let (name, room) = match args {
+		[name, room] => (*name, *room),
+		_ => return Err(usage("reassign")),
+	};
+
+	let mut pairs = [copy_state_in(creep_arg(name)?)];
+	let previous = pairs[0].1.home.clone();
+	let rooms = Object::values(&Game::rooms())
+		.iter()
+		.map(Room::from)
+		.collect::<Vec<_>>();
+	reassign(&mut pairs, name, room, &owned_rooms(&rooms))?;
+	let [(creep, state)] = pairs;
+	copy_state_out(&creep, state)?;
+
+	Ok(format!("{} moved from {} to {}", name, previous, room))
+}
+
+fn colony(args: &[&str]) -> Result<String> {
+	let room = match args {
+		["show", room] => *room,
+		_ => return Err(usage("colony")),
+	};
+
+	let creep_pairs = Object::values(&Game::creeps())
+		.iter()
+		.map(Creep::from)
+		.map(copy_state_in)
+		.collect::<Vec<_>>();
+	let rooms = visible_room(room).into_iter().collect::<Vec<_>>();
+	let spawns = Object::values(&Game::spawns())
+		.iter()
+		.map(StructureSpawn::from)
+		.collect::<Vec<_>>();
+
+	let colony = Colony::gather(&creep_pairs, &rooms, &spawns)
+		.into_iter()
+		.next()
+		.ok_or_else(|| Error::Command(format!("{} is not one of our rooms", room)))?;
+
+	let mut out = format!(
+		"{}: level {}, {} spawns, population target {}\n",
+		colony.name,
+		colony.level(),
+		colony.spawns.len(),
+		colony.population_target()
+	);
+	let economy = economy::get(&colony.name);
+	out.push_str(&format!(
+		"  economy: {:?}, {} stored, {:+.1} per tick\n",
+		economy.tier, economy.stored, economy.income
+	));
+	for (build, count) in &colony.creeps_by_build {
+		out.push_str(&format!("  {:<20} {}\n", build, count));
+	}
+	for (job, count) in &colony.creeps_by_job {
+		out.push_str(&format!("  {:<20} {}\n", job.name(), count));
+	}
+	Ok(out)
+}
+
+fn plan(args: &[&str]) -> Result<String> {
+	let room = match args {
+		["show", room] => *room,
+		_ => return Err(usage("plan")),
+	};
+
+	let visible =
+		visible_room(room).ok_or_else(|| Error::Command(format!("no vision of {}", room)))?;
+
+	let mut planned = HashMap::<String, (u32, u32, u32)>::new();
+	room_find(&visible, Find::MyConstructionSites)
+		.iter()
+		.map(ConstructionSite::from)
+		.for_each(|site| {
+			let entry = planned
+				.entry(format!("{:?}", site.structure_type()))
+				.or_insert((0, 0, 0));
+			entry.0 += 1;
+			entry.1 += site.progress();
+			entry.2 += site.progress_total();
+		});
+
+	if planned.is_empty() {
+		return Ok(format!("nothing planned in {}", room));
+	}
+
+	let mut out = format!("planned in {}\n", room);
+	for (structure_type, (count, progress, total)) in planned {
+		out.push_str(&format!(
+			"  {:<20} {:>3} sites {:>8}/{}\n",
+			structure_type, count, progress, total
+		));
+	}
+	Ok(out)
+}
+
+fn room_intel(args: &[&str]) -> Result<String> {
+	match args {
+		[room] => intel::get(room)
+			.map(|intel| format!("{}: {:?}", room, intel))
+			.ok_or_else(|| Error::Command(format!("nothing known about {}", room))),
+		_ => Err(usage("intel")),
+	}
+}
+
+fn profile(args: &[&str]) -> Result<String> {
+	match args {
+		["on"] => {
+			profiler::set_enabled(true);
+			Ok("profiler enabled".to_string())
+		}
+		["off"] => {
+			profiler::set_enabled(false);
+			Ok("profiler disabled".to_string())
+		}
+		["report"] => Ok(profiler::report(20)),
+		["report", count] => {
+			let count = count.parse().map_err(|_| usage("profile"))?;
+			Ok(profiler::report(count))
+		}
+		_ => Err(usage("profile")),
+	}
+}
+
+fn pixels(args: &[&str]) -> Result<String> {
+	let enabled = match args {
+		["on"] => true,
+		["off"] => false,
+		_ => return Err(usage("pixels")),
+	};
+	config::update(|config| config.pixels.enabled = enabled)?;
+	Ok(format!(
+		"pixel generation {}",
+		if enabled { "on" } else { "off" }
+	))
+}
+
+fn parse_level(level: &str) -> Result<Level> {
+	match level.to_ascii_lowercase().as_str() {
+		"trace" => Ok(Level::Trace),
+		"debug" => Ok(Level::Debug),
+		"info" => Ok(Level::Info),
+		"warn" => Ok(Level::Warn),
+		"error" => Ok(Level::Error),
+		_ => Err(Error::Command(
+			"levels are trace, debug, info, warn, error".to_string(),
+		)),
+	}
+}
+
+fn log(args: &[&str]) -> Result<String> {
+	match args {
+		["level", level] => {
+			let level = parse_level(level)?;
+			config::update(|config| config.logging.level = level)?;
+			Ok(format!("log level {}", level))
+		}
+		["level", level, module] => {
+			let level = parse_level(level)?;
+			config::update(|config| {
+				config.logging.modules.insert(module.to_string(), level);
+			})?;
+			Ok(format!("log level {} for {}", level, module))
+		}
+		["room", "clear"] => {
+			config::update(|config| config.logging.rooms.clear())?;
+			Ok("showing all rooms".to_string())
+		}
+		["room", room] => {
+			config::update(|config| config.logging.rooms.push(room.to_string()))?;
+			Ok(format!("showing {}", room))
+		}
+		["creep", "clear"] => {
+			config::update(|config| config.logging.creeps.clear())?;
+			Ok("showing all creeps".to_string())
+		}
+		["creep", creep] => {
+			config::update(|config| config.logging.creeps.push(creep.to_string()))?;
+			Ok(format!("showing {}", creep))
+		}
+		_ => Err(usage("log")),
+	}
+}
+
+fn visual(args: &[&str]) -> Result<String> {
+	let (room, enabled, layer) = match args {
+		[room, "on"] => (*room, true, None),
+		[room, "off"] => (*room, false, None),
+		[room, "on", layer] => (*room, true, Some(*layer)),
+		[room, "off", layer] => (*room, false, Some(*layer)),
+		_ => return Err(usage("visual")),
+	};
+	let toggle = if enabled { "on" } else { "off" };
+
+	match layer {
+		None => {
+			config::update(|config| {
+				if enabled {
+					config
+						.visuals
+						.rooms
+						.insert(room.to_string(), Layers::default());
+				}
+				else {
+					config.visuals.rooms.remove(room);
+				}
+			})?;
+			Ok(format!("overlays {} in {}", toggle, room))
+		}
+		Some(layer) => {
+			let mut layers = config::with(|config| config.visuals.rooms.get(room).cloned())
+				.unwrap_or_else(Layers::none);
+			if !layers.set(layer, enabled) {
+				return Err(usage("visual"));
+			}
+			config::update(|config| {
+				config.visuals.rooms.insert(room.to_string(), layers);
+			})?;
+			Ok(format!("{} overlay {} in {}", layer, toggle, room))
+		}
+	}
+}
@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+
+use once_cell::sync::Lazy;
+
+use std::sync::Mutex;
+
+use crate::{
+	codec::EncodingConfig,
+	economy::EconomyConfig,
+	error::Result,
+	lifecycle::LifecycleConfig,
+	log_error,
+	logging::LogConfig,
+	stats::StatsConfig,
+	util::{memory_get, memory_set},
+	visuals::VisualsConfig,
+};
+
+/// Key under `Memory` holding the configuration
+const MEMORY_KEY: &str = "config";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PixelConfig {
+	pub enabled: bool,
+	/// Bucket required before a pixel is generated
+	pub min_bucket: i32,
+	/// CPU which must be left unused in the tick budget
+	pub reserve_cpu: f64,
+}
+
+impl Default for PixelConfig {
+	fn default() -> Self {
+		PixelConfig {
+			enabled: false,
+			min_bucket: 10000,
+			reserve_cpu: 2.0,
+		}
+	}
+}
+
+/// Runtime behaviour which can be changed by editing `Memory.config`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+	pub pixels: PixelConfig,
+	pub logging: LogConfig,
+	pub stats: StatsConfig,
+	pub visuals: VisualsConfig,
+	pub encoding: EncodingConfig,
+	pub lifecycle: LifecycleConfig,
+	pub economy: EconomyConfig,
+}
+
+static CONFIG: Lazy<Mutex<Config>> = Lazy::new(|| Mutex::new(Config::default()));
+
+pub fn get() -> Config { CONFIG.lock().unwrap().clone() }
+
+/// Read the configuration without cloning it
+pub fn with<T>(f: impl FnOnce(&Config) -> T) -> T { f(&CONFIG.lock().unwrap()) }
+
+/// Read the configuration from memory, writing the defaults back if there is none. A
+/// configuration which does not parse is reported and left in memory to be fixed, keeping the
+/// previous one in use.
+pub fn load() -> Result<()> {
+	let config = match memory_get(MEMORY_KEY).map(|value| value.into_serde::<Config>()) {
+		Some(Ok(config)) => config,
+		Some(Err(e)) => {
+			log_error!(
+				"invalid Memory.{}, keeping the previous config: {}",
+				MEMORY_KEY,
+				e
+			);
+			return Ok(());
+		}
+		None => Config::default(),
+	};
+	memory_set(MEMORY_KEY, &config)?;
+	*CONFIG.lock().unwrap() = config;
+	Ok(())
+}
+
+pub fn update(f: impl FnOnce(&mut Config)) -> Result<()> {
+	let mut config = CONFIG.lock().unwrap();
+	f(&mut config);
+	memory_set(MEMORY_KEY, &*config)
+}
@@ -0,0 +1,365 @@
+use wasm_bindgen::prelude::*;
+
+use js_sys::Object;
+
+use once_cell::unsync::OnceCell;
+
+use screeps::{
+	constants::{Find, ResourceType, StructureType},
+	objects::{ConstructionSite, Flag, Room, RoomObject, Structure, StructureSpawn},
+	Game, Mineral, Source, StructureExtension,
+};
+
+use std::{cell::RefCell, collections::HashMap};
+
+use crate::{
+	coords::WorldPos,
+	links::{classify, RoomLinks},
+	structures::CreepTarget,
+	util::{dropped_resource, object_id, room_find, room_name, stored, world_pos},
+};
+
+/// Range from the controller within which a container or link feeds upgraders
+const FEED_RANGE: u32 = 3;
+
+/// Objects in one room, indexed when the context is built
+#[derive(Default)]
+struct RoomIndex {
+	construction_sites: Vec<ConstructionSite>,
+	spawns: Vec<StructureSpawn>,
+	structures: HashMap<StructureType, Vec<Structure>>,
+	sources: OnceCell<Vec<Source>>,
+	mineral: OnceCell<Option<Mineral>>,
+	energy_sinks: OnceCell<Vec<Structure>>,
+	controller_feed: OnceCell<Option<Structure>>,
+	energy_piles: OnceCell<Vec<EnergyPile>>,
+	links: OnceCell<RoomLinks>,
+}
+
+/// Energy in a room which creeps can take, from a store, tombstone or ruin or off the ground
+#[derive(Clone)]
+pub struct EnergyPile {
+	pub object: RoomObject,
+	pub target: CreepTarget,
+	pub pos: WorldPos,
+	pub amount: u32,
+	/// Lying on the ground, so picked up rather than withdrawn
+	pub dropped: bool,
+}
+
+impl EnergyPile {
+	fn new(object: RoomObject, amount: u32, dropped: bool) -> Option<Self> {
+		Some(EnergyPile {
+			target: CreepTarget::from_id(&object_id(&object)),
+			pos: world_pos(&object.pos()?)?,
+			object,
+			amount,
+			dropped,
+		})
+	}
+}
+
+/// Game objects gathered once per tick and shared by strategies, planners and jobs. Per room
+/// lookups are indexed up front or computed on first use and kept for the rest of the tick.
+pub struct TickContext {
+	pub time: u32,
+	pub construction_sites: Vec<ConstructionSite>,
+	pub flags: Vec<Flag>,
+	pub rooms: Vec<Room>,
+	pub spawns: Vec<StructureSpawn>,
+	pub structures: Vec<Structure>,
+	by_room: HashMap<String, RoomIndex>,
+	by_pos: OnceCell<HashMap<WorldPos, Vec<Structure>>>,
+	/// Energy claimed from each pile by creeps on their way to collect it
+	reservations: RefCell<HashMap<CreepTarget, u32>>,
+}
+
+impl TickContext {
+	pub fn gather() -> Self {
+		let construction_sites = Object::values(&Game::construction_sites())
+			.iter()
+			.map(ConstructionSite::from)
+			.collect::<Vec<_>>();
+
+		let flags = Object::values(&Game::flags())
+			.iter()
+			.map(Flag::from)
+			.collect::<Vec<_>>();
+
+		let rooms = Object::values(&Game::rooms())
+			.iter()
+			.map(Room::from)
+			.collect::<Vec<_>>();
+
+		let spawns = Object::values(&Game::spawns())
+			.iter()
+			.map(StructureSpawn::from)
+			.collect::<Vec<_>>();
+
+		let structures = Object::values(&Game::structures())
+			.iter()
+			.map(Structure::from)
+			.collect::<Vec<_>>();
+
+		let mut by_room = rooms
+			.iter()
+			.map(|room| (room_name(room), RoomIndex::default()))
+			.collect::<HashMap<_, _>>();
+
+		construction_sites.iter().for_each(|site| {
+			let room = site.pos().map(|pos| pos.room_name().as_string().unwrap());
+			if let Some(index) = room.and_then(|room| by_room.get_mut(&room)) {
+				index.construction_sites.push(site.clone());
+			}
+		});
+		spawns.iter().for_each(|spawner| {
+			let room = spawner.room().map(|room| room_name(&room));
+			if let Some(index) = room.and_then(|room| by_room.get_mut(&room)) {
+				index.spawns.push(spawner.clone());
+			}
+		});
+		structures.iter().for_each(|structure| {
+			let room = structure.room().map(|room| room_name(&room));
+			if let Some(index) = room.and_then(|room| by_room.get_mut(&room)) {
+				index
+					.structures
+					.entry(structure.structure_type())
+					.or_default()
+					.push(structure.clone());
+			}
+		});
+
+		TickContext {
+			time: Game::time(),
+			construction_sites,
+			flags,
+			rooms,
+			spawns,
+			structures,
+			by_room,
+			by_pos: OnceCell::new(),
+			reservations: RefCell::new(HashMap::new()),
+		}
+	}
+
+	pub fn room(&self, name: &str) -> Option<&Room> {
+		self.rooms.iter().find(|room| room_name(room) == name)
+	}
+
+	pub fn construction_sites_in(&self, room: &str) -> &[ConstructionSite] {
+		self.by_room
+			.get(room)
+			.map(|index| index.construction_sites.as_slice())
+			.unwrap_or(&[])
+	}
+
+	pub fn spawns_in(&self, room: &str) -> &[StructureSpawn] {
+		self.by_room
+			.get(room)
+			.map(|index| index.spawns.as_slice())
+			.unwrap_or(&[])
+	}
+
+	/// Our structures of one type in a room
+	pub fn structures_in(&self, room: &str, structure_type: StructureType) -> &[Structure] {
+		self.by_room
+			.get(room)
+			.and_then(|index| index.structures.get(&structure_type))
+			.map(Vec::as_slice)
+			.unwrap_or(&[])
+	}
+
+	/// Our structures standing on a tile
+	pub fn structures_at(&self, pos: WorldPos) -> &[Structure] {
+		self.by_pos
+			.get_or_init(|| {
+				let mut by_pos = HashMap::<_, Vec<_>>::new();
+				self.structures.iter().for_each(|structure| {
+					if let Some(pos) = structure.pos().as_ref().and_then(world_pos) {
+						by_pos.entry(pos).or_default().push(structure.clone());
+					}
+				});
+				by_pos
+			})
+			.get(&pos)
+			.map(Vec::as_slice)
+			.unwrap_or(&[])
+	}
+
+	pub fn sources_in(&self, room: &str) -> &[Source] {
+		match (self.by_room.get(room), self.room(room)) {
+			(Some(index), Some(visible)) => index.sources.get_or_init(|| {
+				room_find(visible, Find::Sources)
+					.iter()
+					.map(Source::from)
+					.collect()
+			}),
+			_ => &[],
+		}
+	}
+
+	/// A room's mineral deposit, every room has at most one
+	pub fn mineral(&self, room: &str) -> Option<&Mineral> {
+		match (self.by_room.get(room), self.room(room)) {
+			(Some(index), Some(visible)) => index
+				.mineral
+				.get_or_init(|| {
+					room_find(visible, Find::Minerals)
+						.iter()
+						.map(Mineral::from)
+						.next()
+				})
+				.as_ref(),
+			_ => None,
+		}
+	}
+
+	/// Spawns and extensions in a room which can take more energy
+	pub fn energy_sinks(&self, room: &str) -> &[Structure] {
+		let index = match self.by_room.get(room) {
+			Some(index) => index,
+			None => return &[],
+		};
+		index.energy_sinks.get_or_init(|| {
+			let spawns = index
+				.spawns
+				.iter()
+				.filter(|x| x.store().get_free_capacity(Some(ResourceType::Energy)) > 0)
+				.map(JsValue::from);
+			let extensions = self
+				.structures_in(room, StructureType::Extension)
+				.iter()
+				.map(JsValue::from)
+				.map(StructureExtension::from)
+				.filter(|x| x.store().get_free_capacity(Some(ResourceType::Energy)) > 0)
+				.map(JsValue::from);
+			spawns.chain(extensions).map(Structure::from).collect()
+		})
+	}
+
+	/// Container or link next to a room's controller, which upgraders draw from. A link is taken
+	/// over a container, so links classify it as the controller link while the container is still
+	/// standing.
+	pub fn controller_feed(&self, room: &str) -> Option<&Structure> {
+		let (index, visible) = match (self.by_room.get(room), self.room(room)) {
+			(Some(index), Some(visible)) => (index, visible),
+			_ => return None,
+		};
+		index
+			.controller_feed
+			.get_or_init(|| {
+				let controller = world_pos(&visible.controller()?.pos()?)?;
+				room_find(visible, Find::Structures)
+					.iter()
+					.map(Structure::from)
+					.filter(|structure| {
+						matches!(
+							structure.structure_type(),
+							StructureType::Container | StructureType::Link
+						)
+					})
+					.filter(|structure| {
+						structure
+							.pos()
+							.as_ref()
+							.and_then(world_pos)
+							.map(|pos| pos.range(&controller) <= FEED_RANGE)
+							.unwrap_or(false)
+					})
+					.min_by_key(|structure| structure.structure_type() != StructureType::Link)
+			})
+			.as_ref()
+	}
+
+	/// Energy lying in a room outside of sources, except the controller's feed which is kept for
+	/// upgraders
+	pub fn energy_piles(&self, room: &str) -> &[EnergyPile] {
+		let (index, visible) = match (self.by_room.get(room), self.room(room)) {
+			(Some(index), Some(visible)) => (index, visible),
+			_ => return &[],
+		};
+		index.energy_piles.get_or_init(|| {
+			let feed = self
+				.controller_feed(room)
+				.and_then(|feed| feed.id().as_string());
+			// Source links are emptied through the network while it has somewhere to send to
+			let senders = self
+				.links(room)
+				.filter(|links| links.receives())
+				.map(|links| {
+					links
+						.sources
+						.iter()
+						.filter_map(|link| link.id().as_string())
+						.collect::<Vec<_>>()
+				})
+				.unwrap_or_default();
+
+			let structures = room_find(visible, Find::Structures)
+				.iter()
+				.filter(|structure| {
+					matches!(
+						Structure::from(structure.clone()).structure_type(),
+						StructureType::Container
+							| StructureType::Storage
+							| StructureType::Link | StructureType::Terminal
+					)
+				})
+				.map(RoomObject::from)
+				.filter(|object| object_id(object).as_string() != feed)
+				.filter(|object| {
+					let id = object_id(object).as_string().unwrap_or_default();
+					!senders.contains(&id)
+				});
+			let remains = room_find(visible, Find::Tombstones)
+				.iter()
+				.chain(room_find(visible, Find::Ruins).iter())
+				.map(RoomObject::from);
+			let stores = structures.chain(remains).filter_map(|object| {
+				let amount = stored(&object, "energy");
+				EnergyPile::new(object, amount, false)
+			});
+
+			let dropped = room_find(visible, Find::DroppedResources)
+				.iter()
+				.map(RoomObject::from)
+				.filter_map(|object| match dropped_resource(&object) {
+					(resource, amount) if resource == "energy" => {
+						EnergyPile::new(object, amount, true)
+					}
+					_ => None,
+				});
+
+			stores
+				.chain(dropped)
+				.filter(|pile| pile.amount > 0)
+				.collect()
+		})
+	}
+
+	/// A room's links sorted by what they are next to, `None` if we do not have vision of it
+	pub fn links(&self, room: &str) -> Option<&RoomLinks> {
+		let index = self.by_room.get(room)?;
+		Some(index.links.get_or_init(|| classify(self, room)))
+	}
+
+	/// Claim energy from a pile for a creep on its way to collect it
+	pub fn reserve(&self, target: &CreepTarget, amount: u32) {
+		*self
+			.reservations
+			.borrow_mut()
+			.entry(target.clone())
+			.or_insert(0) += amount;
+	}
+
+	/// Energy in a pile not yet claimed by another creep
+	pub fn unreserved(&self, pile: &EnergyPile) -> u32 {
+		let reserved = self
+			.reservations
+			.borrow()
+			.get(&pile.target)
+			.copied()
+			.unwrap_or(0);
+		pile.amount.saturating_sub(reserved)
+	}
+}
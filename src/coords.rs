@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+
+use std::{
+	fmt::{Display, Formatter, Result},
+	str::FromStr,
+};
+
+pub const ROOM_SIZE: i32 = 50;
+
+/// Position of a room on the world map. East and south are positive, so `W0N0` is `(-1, -1)` and
+/// `E0S0` is `(0, 0)`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct RoomCoord {
+	pub x: i32,
+	pub y: i32,
+}
+
+impl RoomCoord {
+	pub fn new(x: i32, y: i32) -> Self { RoomCoord { x, y } }
+
+	pub fn parse(name: &str) -> Option<Self> { name.parse().ok() }
+
+	fn axis(value: i32) -> i32 {
+		if value < 0 {
+			-value - 1
+		}
+		else {
+			value
+		}
+	}
+
+	pub fn is_highway(&self) -> bool {
+		Self::axis(self.x) % 10 == 0 || Self::axis(self.y) % 10 == 0
+	}
+
+	pub fn is_center(&self) -> bool {
+		let (x, y) = (Self::axis(self.x) % 10, Self::axis(self.y) % 10);
+		(4..=6).contains(&x) && (4..=6).contains(&y)
+	}
+
+	pub fn is_source_keeper(&self) -> bool {
+		self.is_center() && !(Self::axis(self.x) % 10 == 5 && Self::axis(self.y) % 10 == 5)
+	}
+
+	pub fn neighbours(&self) -> [RoomCoord; 4] {
+		[
+			RoomCoord::new(self.x, self.y - 1),
+			RoomCoord::new(self.x + 1, self.y),
+			RoomCoord::new(self.x, self.y + 1),
+			RoomCoord::new(self.x - 1, self.y),
+		]
+	}
+
+	pub fn distance(&self, other: &RoomCoord) -> i32 {
+		(self.x - other.x).abs().max((self.y - other.y).abs())
+	}
+}
+
+impl Display for RoomCoord {
+	fn fmt(&self, f: &mut Formatter) -> Result {
+		let (h, x) = if self.x < 0 {
+			('W', -self.x - 1)
+		}
+		else {
+			('E', self.x)
+		};
+		let (v, y) = if self.y < 0 {
+			('N', -self.y - 1)
+		}
+		else {
+			('S', self.y)
+		};
+		write!(f, "{}{}{}{}", h, x, v, y)
+	}
+}
+
+impl FromStr for RoomCoord {
+	type Err = ();
+
+	fn from_str(name: &str) -> std::result::Result<Self, ()> {
+		let split = name.find(&['N', 'S'][..]).ok_or(())?;
+		let (horizontal, vertical) = name.split_at(split);
+
+		let parse = |part: &str, negative: char, positive: char| {
+			let mut chars = part.chars();
+			let sign = chars.next().ok_or(())?;
+			let value = chars.as_str().parse::<i32>().map_err(|_| ())?;
+			match sign {
+				s if s == negative => Ok(-value - 1),
+				s if s == positive => Ok(value),
+				_ => Err(()),
+			}
+		};
+
+		Ok(RoomCoord::new(
+			parse(horizontal, 'W', 'E')?,
+			parse(vertical, 'N', 'S')?,
+		))
+	}
+}
+
+/// Absolute tile position on the world map, allowing distances and directions to be computed
+/// across room boundaries
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct WorldPos {
+	pub x: i32,
+	pub y: i32,
+}
+
+impl WorldPos {
+	pub fn new(x: i32, y: i32) -> Self { WorldPos { x, y } }
+
+	pub fn from_local(room: RoomCoord, x: u8, y: u8) -> Self {
+		WorldPos::new(room.x * ROOM_SIZE + x as i32, room.y * ROOM_SIZE + y as i32)
+	}
+
+	pub fn room(&self) -> RoomCoord {
+		RoomCoord::new(self.x.div_euclid(ROOM_SIZE), self.y.div_euclid(ROOM_SIZE))
+	}
+
+	pub fn local(&self) -> (u8, u8) {
+		(
+			self.x.rem_euclid(ROOM_SIZE) as u8,
+			self.y.rem_euclid(ROOM_SIZE) as u8,
+		)
+	}
+
+	pub fn range(&self, other: &WorldPos) -> u32 {
+		(self.x - other.x).abs().max((self.y - other.y).abs()) as u32
+	}
+
+	pub fn is_room_edge(&self) -> bool {
+		let (x, y) = self.local();
+		x == 0 || y == 0 || x == 49 || y == 49
+	}
+
+	/// Screeps direction constant (1 = top, clockwise to 8 = top left) of an adjacent position
+	pub fn direction_to(&self, other: &WorldPos) -> Option<u8> {
+		match ((other.x - self.x).signum(), (other.y - self.y).signum()) {
+			(0, -1) => Some(1),
+			(1, -1) => Some(2),
+			(1, 0) => Some(3),
+			(1, 1) => Some(4),
+			(0, 1) => Some(5),
+			(-1, 1) => Some(6),
+			(-1, 0) => Some(7),
+			(-1, -1) => Some(8),
+			_ => None,
+		}
+	}
+
+	pub fn offset(&self, direction: u8) -> WorldPos {
+		let (dx, dy) = match direction {
+			1 => (0, -1),
+			2 => (1, -1),
+			3 => (1, 0),
+			4 => (1, 1),
+			5 => (0, 1),
+			6 => (-1, 1),
+			7 => (-1, 0),
+			8 => (-1, -1),
+			_ => (0, 0),
+		};
+		WorldPos::new(self.x + dx, self.y + dy)
+	}
+}
@@ -0,0 +1,329 @@
+use wasm_bindgen::prelude::*;
+
+use js_sys::{JsString, Uint8Array};
+
+use once_cell::sync::Lazy;
+
+use screeps::{
+	constants::{Find, Part, StructureType},
+	objects::{ConstructionSite, Creep, Room, Structure, StructureRampart},
+	Game,
+};
+
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::{
+	coords::WorldPos,
+	pathfinding::RoomTerrain,
+	util::{room_find, room_name, visible_room},
+};
+
+pub const ROOM_AREA: usize = 2500;
+pub const IMPASSABLE: u8 = 0xff;
+pub const ROAD_COST: u8 = 1;
+pub const HOSTILE_ZONE_COST: u8 = 50;
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(js_namespace = PathFinder)]
+	pub type CostMatrix;
+
+	#[wasm_bindgen(constructor, js_namespace = PathFinder)]
+	pub fn new() -> CostMatrix;
+
+	#[wasm_bindgen(method)]
+	pub fn set(this: &CostMatrix, x: u8, y: u8, cost: u8);
+
+	type TerrainBuffer;
+
+	#[wasm_bindgen(js_namespace = ["Game", "map"], js_name = getRoomTerrain)]
+	fn get_room_terrain(room: &JsString) -> TerrainBuffer;
+
+	#[wasm_bindgen(method, js_name = getRawBuffer)]
+	fn get_raw_buffer(this: &TerrainBuffer) -> Uint8Array;
+}
+
+/// Which layers to include when building a matrix
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct MatrixOptions {
+	/// Treat our construction sites as the structures they will become
+	pub planned: bool,
+	/// Penalise tiles in range of hostile attackers
+	pub avoid_hostiles: bool,
+	/// Block tiles occupied by creeps
+	pub creeps: bool,
+}
+
+impl Default for MatrixOptions {
+	fn default() -> Self {
+		MatrixOptions {
+			planned: true,
+			avoid_hostiles: true,
+			creeps: false,
+		}
+	}
+}
+
+/// Pathing costs for a single room in plain Rust so they can be cached between ticks and used
+/// without the game runtime. A cost of 0 means the terrain cost applies.
+#[derive(Clone, Debug)]
+pub struct RoomCosts(pub Vec<u8>);
+
+impl Default for RoomCosts {
+	fn default() -> Self { RoomCosts(vec![0; ROOM_AREA]) }
+}
+
+impl RoomCosts {
+	pub fn get(&self, x: u8, y: u8) -> u8 { self.0[y as usize * 50 + x as usize] }
+
+	pub fn set(&mut self, x: u8, y: u8, cost: u8) { self.0[y as usize * 50 + x as usize] = cost; }
+
+	/// Raise a tile to at least `cost`, leaving more expensive tiles alone
+	pub fn raise(&mut self, x: u8, y: u8, cost: u8) {
+		if self.get(x, y) < cost {
+			self.set(x, y, cost);
+		}
+	}
+
+	pub fn to_js(&self) -> CostMatrix {
+		let matrix = CostMatrix::new();
+		self.0
+			.iter()
+			.enumerate()
+			.filter(|(_, cost)| **cost != 0)
+			.for_each(|(index, cost)| matrix.set((index % 50) as u8, (index / 50) as u8, *cost));
+		matrix
+	}
+}
+
+fn walkable(structure_type: StructureType) -> bool {
+	matches!(
+		structure_type,
+		StructureType::Road | StructureType::Container | StructureType::Rampart
+	)
+}
+
+/// Assembles a cost matrix one layer at a time
+#[derive(Default)]
+pub struct CostMatrixBuilder {
+	costs: RoomCosts,
+}
+
+impl CostMatrixBuilder {
+	pub fn new() -> Self { Default::default() }
+
+	pub fn from_costs(costs: RoomCosts) -> Self { CostMatrixBuilder { costs } }
+
+	/// Roads are preferred and anything which cannot be walked over is blocked
+	pub fn structures(mut self, structures: &[Structure]) -> Self {
+		structures.iter().for_each(|structure| {
+			let pos = match structure.pos() {
+				Some(pos) => pos,
+				None => return,
+			};
+			let structure_type = structure.structure_type();
+			if structure_type == StructureType::Road {
+				self.costs.raise(pos.x(), pos.y(), ROAD_COST);
+			}
+			else if structure_type == StructureType::Rampart {
+				let rampart = StructureRampart::from(JsValue::from(structure));
+				if !rampart.my() && !rampart.is_public() {
+					self.costs.set(pos.x(), pos.y(), IMPASSABLE);
+				}
+			}
+			else if !walkable(structure_type) {
+				self.costs.set(pos.x(), pos.y(), IMPASSABLE);
+			}
+		});
+		self
+	}
+
+	/// Construction sites are costed as if already built
+	pub fn planned(mut self, sites: &[ConstructionSite]) -> Self {
+		sites.iter().for_each(|site| {
+			let pos = match site.pos() {
+				Some(pos) => pos,
+				None => return,
+			};
+			let structure_type = site.structure_type();
+			if structure_type == StructureType::Road {
+				self.costs.raise(pos.x(), pos.y(), ROAD_COST);
+			}
+			else if !walkable(structure_type) {
+				self.costs.set(pos.x(), pos.y(), IMPASSABLE);
+			}
+		});
+		self
+	}
+
+	/// Penalise everything in range of hostile attackers
+	pub fn hostile_zones(mut self, hostiles: &[Creep]) -> Self {
+		hostiles.iter().for_each(|hostile| {
+			let range = if hostile.get_active_bodyparts(Part::RangedAttack) > 0 {
+				3
+			}
+			else if hostile.get_active_bodyparts(Part::Attack) > 0 {
+				1
+			}
+			else {
+				return;
+			};
+			let pos = match hostile.pos() {
+				Some(pos) => pos,
+				None => return,
+			};
+			let (x, y) = (pos.x() as i32, pos.y() as i32);
+			for dy in -range..=range {
+				for dx in -range..=range {
+					let (x, y) = (x + dx, y + dy);
+					if (0..50).contains(&x) && (0..50).contains(&y) {
+						self.costs.raise(x as u8, y as u8, HOSTILE_ZONE_COST);
+					}
+				}
+			}
+		});
+		self
+	}
+
+	pub fn creeps(mut self, creeps: &[Creep]) -> Self {
+		creeps
+			.iter()
+			.filter_map(|creep| creep.pos())
+			.for_each(|pos| {
+				self.costs.set(pos.x(), pos.y(), IMPASSABLE);
+			});
+		self
+	}
+
+	/// Block arbitrary tiles of this room
+	pub fn blocked(mut self, room: &str, positions: &[WorldPos]) -> Self {
+		positions
+			.iter()
+			.filter(|pos| pos.room().to_string() == room)
+			.for_each(|pos| {
+				let (x, y) = pos.local();
+				self.costs.set(x, y, IMPASSABLE);
+			});
+		self
+	}
+
+	pub fn build(self) -> RoomCosts { self.costs }
+}
+
+struct CachedCosts {
+	costs: RoomCosts,
+	fingerprint: u32,
+	created: u32,
+	/// Tick the fingerprint was last compared, so a room is only scanned once per tick
+	checked: u32,
+}
+
+/// Static layers are cached per room and option set. Hostile and creep layers move every tick so
+/// they are applied on top of the cached matrix when requested.
+static CACHE: Lazy<Mutex<HashMap<(String, bool), CachedCosts>>> =
+	Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Terrain never changes, so it is cached for the lifetime of the VM
+static TERRAIN: Lazy<Mutex<HashMap<String, RoomTerrain>>> =
+	Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Terrain of any room, visible or not, for use with the native pathfinder
+pub fn terrain(room: &str) -> RoomTerrain {
+	TERRAIN
+		.lock()
+		.unwrap()
+		.entry(room.to_string())
+		.or_insert_with(|| {
+			RoomTerrain::from_raw(
+				&get_room_terrain(&JsString::from(room))
+					.get_raw_buffer()
+					.to_vec(),
+			)
+		})
+		.clone()
+}
+
+/// Cheap summary of what is built in a room, used to notice when structures change
+fn fingerprint(room: &Room) -> u32 {
+	(room_find(room, Find::Structures).length() << 16)
+		| room_find(room, Find::MyConstructionSites).length()
+}
+
+/// Matrix for a room. Falls back to the last cached matrix if the room is not visible, and to
+/// `None` if we have never seen it.
+pub fn room_costs(room: &str, options: MatrixOptions) -> Option<RoomCosts> {
+	let key = (room.to_string(), options.planned);
+	let visible = visible_room(room);
+
+	let mut cache = CACHE.lock().unwrap();
+
+	let time = Game::time();
+	let checked = cache
+		.get(&key)
+		.map(|cached| cached.checked == time)
+		.unwrap_or(false);
+	if let Some(visible) = visible.as_ref().filter(|_| !checked) {
+		let fingerprint = fingerprint(visible);
+		let cached = cache
+			.get_mut(&key)
+			.filter(|cached| cached.fingerprint == fingerprint);
+		if let Some(cached) = cached {
+			cached.checked = time;
+		}
+		else {
+			let structures = room_find(visible, Find::Structures)
+				.iter()
+				.map(Structure::from)
+				.collect::<Vec<_>>();
+			let mut builder = CostMatrixBuilder::new().structures(&structures);
+			if options.planned {
+				let sites = room_find(visible, Find::MyConstructionSites)
+					.iter()
+					.map(ConstructionSite::from)
+					.collect::<Vec<_>>();
+				builder = builder.planned(&sites);
+			}
+			cache.insert(
+				key.clone(),
+				CachedCosts {
+					costs: builder.build(),
+					fingerprint,
+					created: time,
+					checked: time,
+				},
+			);
+		}
+	}
+
+	let costs = cache.get(&key)?.costs.clone();
+	drop(cache);
+
+	let mut builder = CostMatrixBuilder::from_costs(costs);
+	if let Some(visible) = &visible {
+		if options.avoid_hostiles {
+			let hostiles = room_find(visible, Find::HostileCreeps)
+				.iter()
+				.map(Creep::from)
+				.collect::<Vec<_>>();
+			builder = builder.hostile_zones(&hostiles);
+		}
+		if options.creeps {
+			let creeps = room_find(visible, Find::Creeps)
+				.iter()
+				.map(Creep::from)
+				.collect::<Vec<_>>();
+			builder = builder.creeps(&creeps);
+		}
+	}
+
+	Some(builder.build())
+}
+
+/// Forget matrices for rooms we have not seen in a long time
+pub fn prune(rooms: &[Room], max_age: u32) {
+	let time = Game::time();
+	let visible = rooms.iter().map(room_name).collect::<Vec<_>>();
+	CACHE.lock().unwrap().retain(|(room, _), cached| {
+		visible.contains(room) || time.saturating_sub(cached.created) < max_age
+	});
+}
@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+
+use once_cell::sync::Lazy;
+
+use screeps::{constants::ResourceType, Game};
+
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::{colony::Colony, config};
+
+/// Weight of the latest sample in the smoothed income
+const INCOME_SMOOTHING: f64 = 0.02;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EconomyConfig {
+	/// Storage energy below which a room saves rather than spends
+	pub low: u32,
+	/// Storage energy above which a room spends freely
+	pub surplus: u32,
+}
+
+impl Default for EconomyConfig {
+	fn default() -> Self {
+		EconomyConfig {
+			low: 50_000,
+			surplus: 500_000,
+		}
+	}
+}
+
+/// How much a room can afford to spend, from its storage level
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Tier {
+	/// No storage yet, so nothing to budget from or save into
+	Unbanked,
+	/// Building up a reserve
+	Low,
+	Normal,
+	/// Pumping energy into the controller and walls
+	Surplus,
+}
+
+impl Default for Tier {
+	fn default() -> Self { Self::Unbanked }
+}
+
+/// What a room spends energy on, read by the spawner and the job planners
+#[derive(Clone, Copy, Debug)]
+pub struct Policy {
+	pub max_upgraders: u16,
+	pub max_builders: u16,
+	/// Hits walls and ramparts are repaired up to, none while saving or without a storage
+	pub wall_hits: u32,
+	/// Energy nothing else needs goes into the storage rather than the controller
+	pub bank: bool,
+}
+
+impl Tier {
+	pub fn policy(&self) -> Policy {
+		match self {
+			// Every bit of energy goes into growing the room, none into walls
+			Tier::Unbanked => Policy {
+				max_upgraders: 5,
+				max_builders: 3,
+				wall_hits: 0,
+				bank: false,
+			},
+			Tier::Low => Policy {
+				max_upgraders: 1,
+				max_builders: 1,
+				wall_hits: 0,
+				bank: true,
+			},
+			Tier::Normal => Policy {
+				max_upgraders: 5,
+				max_builders: 3,
+				wall_hits: 100_000,
+				bank: false,
+			},
+			Tier::Surplus => Policy {
+				max_upgraders: 8,
+				max_builders: 3,
+				wall_hits: 10_000_000,
+				bank: false,
+			},
+		}
+	}
+}
+
+/// Storage level and trend of one room
+#[derive(Clone, Copy, Default, Debug)]
+pub struct RoomEconomy {
+	pub stored: u32,
+	/// Smoothed change in storage energy per tick
+	pub income: f64,
+	pub tier: Tier,
+	last_time: u32,
+}
+
+static ECONOMY: Lazy<Mutex<HashMap<String, RoomEconomy>>> =
+	Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Sample the storage of every colony. Rooms without a storage have nothing to budget and are
+/// left unbanked.
+pub fn update(colonies: &[Colony]) {
+	let config = config::with(|config| config.economy.clone());
+	let time = Game::time();
+	let mut economy = ECONOMY.lock().unwrap();
+
+	economy.retain(|room, _| colonies.iter().any(|colony| &colony.name == room));
+	for colony in colonies {
+		let storage = match colony.room.storage() {
+			Some(storage) => storage,
+			None => {
+				economy.remove(&colony.name);
+				continue;
+			}
+		};
+		let stored = storage
+			.store()
+			.get_used_capacity(Some(ResourceType::Energy));
+
+		let room = economy.entry(colony.name.clone()).or_insert(RoomEconomy {
+			stored,
+			last_time: time,
+			..Default::default()
+		});
+		let elapsed = time.saturating_sub(room.last_time).max(1);
+		let sample = (stored as f64 - room.stored as f64) / elapsed as f64;
+		room.income += (sample - room.income) * INCOME_SMOOTHING;
+		room.stored = stored;
+		room.last_time = time;
+		room.tier = if stored < config.low {
+			Tier::Low
+		}
+		else if stored >= config.surplus {
+			Tier::Surplus
+		}
+		else {
+			Tier::Normal
+		};
+	}
+}
+
+pub fn get(room: &str) -> RoomEconomy {
+	ECONOMY
+		.lock()
+		.unwrap()
+		.get(room)
+		.copied()
+		.unwrap_or_default()
+}
+
+pub fn policy(room: &str) -> Policy { get(room).tier.policy() }
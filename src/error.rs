@@ -6,14 +6,22 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub enum Error {
 	#[error(transparent)]
 	Serialize(#[from] serde_json::error::Error),
-	#[error("could not read state of creep {0} at [{1}, {2}] ")]
-	Deserialize(String, u8, u8),
+	#[error(transparent)]
+	Binary(#[from] bincode::Error),
+	#[error("could not unpack encoded data")]
+	Unpack,
+	#[error("binary data is from format version {0}, expected {1}")]
+	Version(u8, u8),
 	#[error("could not resolve ID to value")]
 	IDResolve,
 	#[error("No targets found")]
 	NoneFound,
 	#[error("Encountered an unhandled error code while performing action: {0:?}")]
 	UnhandledErrorCode(screeps::constants::ReturnCode),
+	#[error("{0}")]
+	Command(String),
+	#[error("segment error: {0}")]
+	Segment(String),
 	#[error("Unknown error")]
 	Unknown,
 }
@@ -0,0 +1,183 @@
+use serde::{Deserialize, Serialize};
+
+use once_cell::sync::Lazy;
+
+use screeps::{
+	constants::{Find, Part, StructureType},
+	objects::{Creep, Room, Structure},
+	Game,
+};
+
+use std::{
+	collections::HashMap,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Mutex,
+	},
+};
+
+use crate::{
+	codec, config,
+	coords::{RoomCoord, WorldPos},
+	error::Result,
+	log_warn,
+	segments::{self, SegmentKind},
+	util::{room_find, room_name, world_pos},
+};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RoomIntel {
+	pub owner: Option<String>,
+	pub reserved_by: Option<String>,
+	pub mine: bool,
+	pub level: u8,
+	pub hostile_creeps: u16,
+	pub hostile_towers: u16,
+	pub last_seen: u32,
+}
+
+impl RoomIntel {
+	/// Owned or reserved by another player
+	pub fn foreign(&self) -> bool {
+		!self.mine && (self.owner.is_some() || self.reserved_by.is_some())
+	}
+
+	/// Rooms which we should not path through
+	pub fn hostile(&self) -> bool {
+		(self.owner.is_some() && !self.mine) || self.hostile_towers > 0
+	}
+}
+
+static INTEL: Lazy<Mutex<HashMap<String, RoomIntel>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Whether the database has been read back from its segments since the last global reset
+static RESTORED: AtomicBool = AtomicBool::new(false);
+
+/// Load the database saved before a global reset, once its segments are active. Rooms seen since
+/// the reset keep their newer entries.
+pub fn restore() -> Result<()> {
+	if RESTORED.load(Ordering::Relaxed) {
+		return Ok(());
+	}
+	let data = match segments::read(SegmentKind::Intel) {
+		Ok(Some(data)) => data,
+		Ok(None) => return Ok(()),
+		Err(e) => {
+			log_warn!("discarding saved intel: {}", e);
+			Vec::new()
+		}
+	};
+	RESTORED.store(true, Ordering::Relaxed);
+	if data.is_empty() {
+		return Ok(());
+	}
+
+	let saved = match codec::from_bytes::<HashMap<String, RoomIntel>>(&data) {
+		Ok(saved) => saved,
+		Err(e) => {
+			// Rebuilt from vision, like after a first deploy
+			log_warn!("discarding saved intel: {}", e);
+			return Ok(());
+		}
+	};
+	let mut intel = INTEL.lock().unwrap();
+	saved.into_iter().for_each(|(room, saved)| {
+		intel.entry(room).or_insert(saved);
+	});
+	Ok(())
+}
+
+/// Persist the database so it survives global resets
+pub fn save() -> Result<()> {
+	if !RESTORED.load(Ordering::Relaxed) {
+		return Ok(());
+	}
+	let encoding = config::with(|config| config.encoding.intel);
+	let data = codec::to_bytes(&*INTEL.lock().unwrap(), encoding)?;
+	segments::write(SegmentKind::Intel, &data)
+}
+
+/// Refresh the database from every room we currently have vision of
+pub fn gather(rooms: &[Room]) {
+	let mut intel = INTEL.lock().unwrap();
+	let time = Game::time();
+
+	rooms.iter().for_each(|room| {
+		let controller = room.controller();
+
+		let owner = controller
+			.as_ref()
+			.and_then(|c| c.owner())
+			.and_then(|o| o.username().as_string());
+		let reserved_by = controller
+			.as_ref()
+			.and_then(|c| c.reservation())
+			.and_then(|r| r.username().as_string());
+		let mine = controller.as_ref().map(|c| c.my()).unwrap_or(false);
+		let level = controller.as_ref().map(|c| c.level()).unwrap_or(0);
+
+		let hostile_creeps = room_find(room, Find::HostileCreeps).length() as u16;
+		let hostile_towers = room_find(room, Find::HostileStructures)
+			.iter()
+			.map(Structure::from)
+			.filter(|s| s.structure_type() == StructureType::Tower)
+			.count() as u16;
+
+		intel.insert(
+			room_name(room),
+			RoomIntel {
+				owner,
+				reserved_by,
+				mine,
+				level,
+				hostile_creeps,
+				hostile_towers,
+				last_seen: time,
+			},
+		);
+	});
+}
+
+pub fn get(room: &str) -> Option<RoomIntel> { INTEL.lock().unwrap().get(room).cloned() }
+
+/// Whether any of our rooms currently has hostile creeps in it
+pub fn any_threat() -> bool {
+	INTEL
+		.lock()
+		.unwrap()
+		.values()
+		.any(|intel| intel.mine && intel.hostile_creeps > 0)
+}
+
+/// Hostile creeps able to attack in a room, with the range they can hit from
+pub fn threats(room: &Room) -> Vec<(WorldPos, u32)> {
+	room_find(room, Find::HostileCreeps)
+		.iter()
+		.map(Creep::from)
+		.filter_map(|hostile| {
+			let range = if hostile.get_active_bodyparts(Part::RangedAttack) > 0 {
+				3
+			}
+			else if hostile.get_active_bodyparts(Part::Attack) > 0 {
+				1
+			}
+			else {
+				return None;
+			};
+			Some((world_pos(&hostile.pos()?)?, range))
+		})
+		.collect()
+}
+
+/// Cost of crossing a room at the route level, or `None` if the room should be avoided
+pub fn route_cost(room: &str) -> Option<f64> {
+	let coord = RoomCoord::parse(room)?;
+	match get(room) {
+		Some(intel) if intel.hostile() => None,
+		Some(intel) if intel.foreign() => Some(5.0),
+		Some(intel) if intel.mine => Some(1.0),
+		_ if coord.is_highway() => Some(1.0),
+		_ if coord.is_source_keeper() => Some(10.0),
+		_ => Some(2.5),
+	}
+}
@@ -0,0 +1,246 @@
+use wasm_bindgen::prelude::*;
+
+use serde::{Deserialize, Serialize};
+
+use screeps::{
+	constants::{ResourceType, StructureType},
+	objects::Creep,
+	StructureTower,
+};
+
+use std::{collections::HashMap, mem::replace};
+
+use crate::{
+	builds::CreepBuild,
+	colony::Colony,
+	context::TickContext,
+	coords::WorldPos,
+	intel,
+	jobs::{downgrading, CreepJob, DistributeEnergyState, UpgradeState},
+	log_debug,
+	logging::Context,
+	structures::{CreepState, CreepTarget},
+	util::{room_name, ticks_to_live, world_pos},
+};
+
+const TOWER_LOW_ENERGY: u32 = 200;
+/// Ticks to live below which a creep drops its work to deliver what it carries
+const DYING_TICKS: u32 = 30;
+
+/// Urgent work which takes a creep away from its job. New interrupts go at the end, since the
+/// variant index is what the binary encoding stores, and are ranked by `urgency`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Interrupt {
+	/// Spawns and extensions hold too little energy to spawn anything
+	SpawnStarved,
+	/// A tower is running dry while hostiles are in the room
+	TowerEmpty,
+	/// The creep will die before its job is done
+	Dying,
+	/// A hostile attacker is within reach
+	HostileAdjacent,
+	/// The controller is close to losing a level
+	Downgrading,
+}
+
+impl Interrupt {
+	/// Rank against other interrupts, a creep only being taken over by a more urgent one
+	fn urgency(&self) -> u8 {
+		match self {
+			Interrupt::Downgrading => 0,
+			Interrupt::SpawnStarved => 1,
+			Interrupt::TowerEmpty => 2,
+			Interrupt::Dying => 3,
+			Interrupt::HostileAdjacent => 4,
+		}
+	}
+
+	/// Whether the interrupted job is picked up again afterwards
+	fn resumes(&self) -> bool { !matches!(self, Interrupt::Dying) }
+
+	/// Creeps per colony sent to deal with it, leaving the rest at work
+	fn responders(&self) -> usize {
+		match self {
+			Interrupt::Downgrading => 1,
+			Interrupt::SpawnStarved | Interrupt::TowerEmpty => 2,
+			Interrupt::Dying | Interrupt::HostileAdjacent => usize::MAX,
+		}
+	}
+}
+
+/// Energy below which spawns cannot produce even the cheapest build. This stays under the 300 of a
+/// room with only its spawn, so such a room is not starved just for being below capacity.
+fn starved_energy() -> u32 {
+	CreepBuild::ALL
+		.iter()
+		.map(|build| build.parts().cost())
+		.min()
+		.unwrap_or(0)
+}
+
+/// What is going wrong in a colony this tick
+struct Alerts {
+	downgrading: bool,
+	starved: bool,
+	empty_tower: Option<CreepTarget>,
+}
+
+impl Alerts {
+	fn new(ctx: &TickContext, colony: &Colony) -> Self {
+		let room = &colony.room;
+		let under_attack = intel::get(&colony.name)
+			.map(|intel| intel.hostile_creeps > 0)
+			.unwrap_or(false);
+
+		let empty_tower = if under_attack {
+			ctx.structures_in(&colony.name, StructureType::Tower)
+				.iter()
+				.map(JsValue::from)
+				.map(StructureTower::from)
+				.filter(|tower| {
+					tower.store().get_used_capacity(Some(ResourceType::Energy)) < TOWER_LOW_ENERGY
+				})
+				.map(|tower| CreepTarget::from_id(&tower.id()))
+				.next()
+		}
+		else {
+			None
+		};
+
+		Alerts {
+			downgrading: downgrading(room),
+			starved: room.energy_available() < starved_energy(),
+			empty_tower,
+		}
+	}
+}
+
+/// Most urgent interrupt which applies to a creep, with the job to deal with it
+fn detect(
+	creep: &Creep,
+	state: &CreepState,
+	alerts: Option<&Alerts>,
+	threats: &[(WorldPos, u32)],
+) -> Option<(Interrupt, CreepJob)> {
+	let pos = world_pos(&creep.pos()?)?;
+	let in_reach = threats
+		.iter()
+		.any(|(threat, range)| threat.range(&pos) <= range + 1);
+	if in_reach && !matches!(state.job, CreepJob::Flee(_)) {
+		return Some((
+			Interrupt::HostileAdjacent,
+			CreepJob::Flee(Default::default()),
+		));
+	}
+
+	if creep.store().get_used_capacity(Some(ResourceType::Energy)) == 0 {
+		return None;
+	}
+	let distributing = matches!(state.job, CreepJob::DistributeEnergy(_));
+
+	let dying = ticks_to_live(creep)
+		.map(|ticks| ticks < DYING_TICKS)
+		.unwrap_or(false);
+	if dying && !distributing {
+		return Some((
+			Interrupt::Dying,
+			CreepJob::DistributeEnergy(Default::default()),
+		));
+	}
+
+	let alerts = alerts?;
+	if let Some(tower) = &alerts.empty_tower {
+		if state.job.target() != Some(tower) {
+			return Some((
+				Interrupt::TowerEmpty,
+				CreepJob::DistributeEnergy(DistributeEnergyState::Distributing(tower.clone())),
+			));
+		}
+	}
+	if alerts.starved && !distributing {
+		return Some((
+			Interrupt::SpawnStarved,
+			CreepJob::DistributeEnergy(Default::default()),
+		));
+	}
+	if alerts.downgrading && !matches!(state.job, CreepJob::Upgrade(_)) {
+		return Some((
+			Interrupt::Downgrading,
+			CreepJob::Upgrade(UpgradeState::Upgrading),
+		));
+	}
+
+	None
+}
+
+/// Replace a creep's job with urgent work, suspending the job if it can be resumed
+fn preempt(state: &mut CreepState, interrupt: Interrupt, job: CreepJob) {
+	let mut previous = replace(&mut state.job, job);
+	previous.preempted();
+
+	if !interrupt.resumes() {
+		state.suspended = None;
+	}
+	else if state.interrupt.is_none() && previous.resumable() {
+		state.suspended = Some(previous);
+	}
+	state.interrupt = Some(interrupt);
+}
+
+/// Release a creep from a finished job, resuming the job an interrupt suspended
+pub fn finish(state: &mut CreepState) {
+	state.interrupt = None;
+	state.job = state.suspended.take().unwrap_or(CreepJob::None);
+}
+
+/// Interrupt creeps whose jobs are less urgent than something happening around them
+pub fn apply(ctx: &TickContext, creep_pairs: &mut [(Creep, CreepState)], colonies: &[Colony]) {
+	let alerts = colonies
+		.iter()
+		.map(|colony| (colony.name.clone(), Alerts::new(ctx, colony)))
+		.collect::<HashMap<_, _>>();
+
+	let mut responders = HashMap::<(String, Interrupt), usize>::new();
+	creep_pairs.iter().for_each(|(_, state)| {
+		if let Some(interrupt) = state.interrupt {
+			*responders
+				.entry((state.home.clone(), interrupt))
+				.or_insert(0) += 1;
+		}
+	});
+
+	let mut threats = HashMap::<String, Vec<(WorldPos, u32)>>::new();
+
+	for (creep, state) in creep_pairs.iter_mut() {
+		let room = match creep.room() {
+			Some(room) => room,
+			None => continue,
+		};
+		let threats = threats
+			.entry(room_name(&room))
+			.or_insert_with(|| intel::threats(&room));
+
+		let (interrupt, job) = match detect(creep, state, alerts.get(&state.home), threats) {
+			Some(detected) => detected,
+			None => continue,
+		};
+		let outranked = state
+			.interrupt
+			.map(|current| interrupt.urgency() <= current.urgency())
+			.unwrap_or(false);
+		if outranked {
+			continue;
+		}
+		let count = responders
+			.entry((state.home.clone(), interrupt))
+			.or_insert(0);
+		if *count >= interrupt.responders() {
+			continue;
+		}
+		*count += 1;
+
+		let context = Context::creep(creep.name().as_string().unwrap(), Some(state.home.clone()));
+		log_debug!(ctx: context, "{:?} interrupts {}", interrupt, state.job.label());
+		preempt(state, interrupt, job);
+	}
+}
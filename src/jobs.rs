@@ -1,5 +1,14 @@
+mod build;
+mod collect;
 mod distribute_energy;
+mod flee;
 mod harvest;
+mod haul;
+mod mine;
+mod recycle;
+mod renew;
+mod repair;
+mod upgrade;
 
 use wasm_bindgen::prelude::*;
 
@@ -7,47 +16,263 @@ use js_sys::Array;
 
 use serde::{Deserialize, Serialize};
 
-use screeps::objects::{ConstructionSite, Creep, Flag, Room, Structure, StructureSpawn};
+use screeps::{constants::Part, objects::Creep};
 
-use std::{
-	hash::{Hash, Hasher},
-	mem::discriminant,
-};
+use std::collections::HashMap;
 
 use crate::{
+	colony::Colony,
+	context::TickContext,
 	error::Result,
-	structures::{CreepParts, CreepState},
+	market::Offer,
+	structures::{CreepParts, CreepState, CreepTarget},
+};
+
+pub use self::{
+	build::BuildState,
+	collect::{reserve_energy, CollectState},
+	distribute_energy::DistributeEnergyState,
+	flee::FleeState,
+	harvest::HarvestState,
+	haul::HaulState,
+	mine::{mineable, MineState},
+	recycle::RecycleState,
+	renew::RenewState,
+	repair::RepairState,
+	upgrade::{downgrading, UpgradeState},
 };
 
 pub trait Job: Default {
 	fn finished(&self) -> bool;
 
-	fn min_required(&self) -> CreepParts;
-
-	fn drive(
-		&mut self,
-		creep: &Creep,
-		construction_sites: &[ConstructionSite],
-		flags: &[Flag],
-		rooms: &[Room],
-		spawns: &[StructureSpawn],
-		structures: &[Structure],
-	) -> Result<()>;
+	fn state_name(&self) -> &'static str;
+
+	fn target(&self) -> Option<&CreepTarget>;
+
+	/// Whether the job can be picked up again once the work which interrupted it is done
+	fn resumable(&self) -> bool { true }
+
+	/// Called when urgent work takes over the creep, to release anything the job holds
+	fn preempted(&mut self) {}
+
+	fn drive(&mut self, creep: &Creep, ctx: &TickContext) -> Result<()>;
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub enum CreepJob {
-	None,
-	Harvest(harvest::HarvestState),
-	DistributeEnergy(distribute_energy::DistributeEnergyState),
+/// Declares every job once, generating `CreepJob` with one variant per job state, the `JobKind`
+/// tag used for counting and comparing jobs, dispatch to the `Job` trait and the planner list.
+/// The variant name is also the serde tag and the name used in stats and logs. New jobs go at the
+/// end, since the variant index is what the binary encoding stores.
+macro_rules! job_registry {
+	($(
+		$(#[$doc:meta])*
+		$kind:ident($state:ty) {
+			priority: $priority:expr,
+			parts: [$(($part:expr, $count:expr)),* $(,)?],
+			colour: $colour:expr,
+			planners: [$($planner:path),* $(,)?] $(,)?
+		}
+	),* $(,)?) => {
+		#[derive(Clone, Debug, Serialize, Deserialize)]
+		pub enum CreepJob {
+			None,
+			$($(#[$doc])* $kind($state),)*
+		}
+
+		#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+		pub enum JobKind {
+			None,
+			$($kind,)*
+		}
+
+		impl JobKind {
+			pub const ALL: &'static [JobKind] = &[JobKind::None, $(JobKind::$kind,)*];
+
+			pub fn name(&self) -> &'static str {
+				match self {
+					JobKind::None => "None",
+					$(JobKind::$kind => stringify!($kind),)*
+				}
+			}
+
+			/// Base priority of the job's offers
+			pub fn priority(&self) -> u8 {
+				match self {
+					JobKind::None => 0,
+					$(JobKind::$kind => $priority,)*
+				}
+			}
+
+			/// Parts a creep needs to take the job
+			pub fn min_required(&self) -> CreepParts {
+				match self {
+					JobKind::None => CreepParts(Default::default()),
+					$(JobKind::$kind => CreepParts(
+						[$(($part, $count)),*].iter().copied().collect::<HashMap<_, _>>(),
+					),)*
+				}
+			}
+
+			/// Colour of creeps doing the job in the debug overlay
+			pub fn colour(&self) -> &'static str {
+				match self {
+					JobKind::None => "#888888",
+					$(JobKind::$kind => $colour,)*
+				}
+			}
+		}
+
+		impl CreepJob {
+			pub fn kind(&self) -> JobKind {
+				match self {
+					CreepJob::None => JobKind::None,
+					$(CreepJob::$kind(_) => JobKind::$kind,)*
+				}
+			}
+
+			pub fn finished(&self) -> bool {
+				match self {
+					CreepJob::None => false,
+					$(CreepJob::$kind(job_state) => job_state.finished(),)*
+				}
+			}
+
+			pub fn state_name(&self) -> &'static str {
+				match self {
+					CreepJob::None => "",
+					$(CreepJob::$kind(job_state) => job_state.state_name(),)*
+				}
+			}
+
+			pub fn target(&self) -> Option<&CreepTarget> {
+				match self {
+					CreepJob::None => None,
+					$(CreepJob::$kind(job_state) => job_state.target(),)*
+				}
+			}
+
+			pub fn resumable(&self) -> bool {
+				match self {
+					CreepJob::None => false,
+					$(CreepJob::$kind(job_state) => job_state.resumable(),)*
+				}
+			}
+
+			pub fn preempted(&mut self) {
+				match self {
+					CreepJob::None => (),
+					$(CreepJob::$kind(job_state) => job_state.preempted(),)*
+				}
+			}
+
+			pub fn drive(&mut self, creep: &Creep, ctx: &TickContext) -> Result<()> {
+				match self {
+					CreepJob::None => Ok(()),
+					$(CreepJob::$kind(job_state) => job_state.drive(creep, ctx),)*
+				}
+			}
+		}
+
+		/// Planners publishing the offers of each job type for a colony
+		pub const PLANNERS: &[fn(&Colony, &TickContext) -> Vec<Offer>] = &[$($($planner,)*)*];
+	};
 }
 
-impl PartialEq for CreepJob {
-	fn eq(&self, other: &Self) -> bool { discriminant(self) == discriminant(other) }
+job_registry! {
+	/// Mine a source and carry the energy home
+	Harvest(HarvestState) {
+		priority: 50,
+		parts: [(Part::Move, 1), (Part::Work, 1), (Part::Carry, 1)],
+		colour: "#ffe56d",
+		planners: [harvest::offers],
+	},
+	/// Fill spawns, extensions and towers, or build when they are full
+	DistributeEnergy(DistributeEnergyState) {
+		priority: 40,
+		parts: [(Part::Move, 1), (Part::Work, 1), (Part::Carry, 1)],
+		colour: "#6dc1ff",
+		planners: [distribute_energy::offers],
+	},
+	/// Only taken through an interrupt, so it has no planner
+	Flee(FleeState) {
+		priority: u8::MAX,
+		parts: [(Part::Move, 1)],
+		colour: "#ff5050",
+		planners: [],
+	},
+	/// Assigned by the lifecycle pass to creeps worth keeping alive
+	Renew(RenewState) {
+		priority: 0,
+		parts: [(Part::Move, 1)],
+		colour: "#9dff6d",
+		planners: [],
+	},
+	/// Assigned by the lifecycle pass to creeps with an obsolete build
+	Recycle(RecycleState) {
+		priority: 0,
+		parts: [],
+		colour: "#b06dff",
+		planners: [],
+	},
+	/// Dedicated to upgraders, or taken through an interrupt when the controller is downgrading
+	Upgrade(UpgradeState) {
+		priority: 30,
+		parts: [(Part::Move, 1), (Part::Work, 1), (Part::Carry, 1)],
+		colour: "#ffffff",
+		planners: [upgrade::offers],
+	},
+	/// Dedicated to builders, working through construction sites in priority order
+	Build(BuildState) {
+		priority: 35,
+		parts: [(Part::Move, 1), (Part::Work, 1), (Part::Carry, 1)],
+		colour: "#ff9f40",
+		planners: [build::offers],
+	},
+	/// Take energy lying around in the colony before going to the sources
+	Collect(CollectState) {
+		priority: 55,
+		parts: [(Part::Move, 1), (Part::Carry, 1)],
+		colour: "#ffb6e1",
+		planners: [collect::offers],
+	},
+	/// Keep structures in shape, and walls up to the level the room's economy allows
+	Repair(RepairState) {
+		priority: 45,
+		parts: [(Part::Move, 1), (Part::Work, 1), (Part::Carry, 1)],
+		colour: "#c8c8c8",
+		planners: [repair::offers],
+	},
+	/// Dedicated to mineral miners, from the level a room can have an extractor
+	Mine(MineState) {
+		priority: 50,
+		parts: [(Part::Move, 1), (Part::Work, 1), (Part::Carry, 1)],
+		colour: "#6dffd8",
+		planners: [mine::offers],
+	},
+	/// Carry minerals from the mining container to the terminal or storage
+	Haul(HaulState) {
+		priority: 20,
+		parts: [(Part::Move, 1), (Part::Carry, 1)],
+		colour: "#d8ff6d",
+		planners: [haul::offers],
+	},
 }
 
-impl Eq for CreepJob {}
+impl CreepJob {
+	pub fn name(&self) -> &'static str { self.kind().name() }
+
+	/// Parts a creep needs to take this job
+	pub fn min_required(&self) -> CreepParts { self.kind().min_required() }
+
+	/// Job and state name, for logs and profiling
+	pub fn label(&self) -> String { self.kind().label(self.state_name()) }
+}
 
-impl Hash for CreepJob {
-	fn hash<H: Hasher>(&self, state: &mut H) { discriminant(self).hash(state); }
+impl JobKind {
+	/// Label of a job in a given state, which can be built after the job has moved on
+	pub fn label(&self, state_name: &str) -> String {
+		match self {
+			JobKind::None => self.name().to_string(),
+			_ => format!("{}/{}", self.name(), state_name),
+		}
+	}
 }
@@ -0,0 +1,210 @@
+use wasm_bindgen::prelude::*;
+
+use js_sys::{Array, JsString};
+
+use serde::{Deserialize, Serialize};
+
+use screeps::{
+	constants::{ResourceType, ReturnCode, StructureType},
+	objects::{ConstructionSite, Creep, RoomObject, RoomPosition},
+};
+
+use num_traits::cast::FromPrimitive;
+
+use crate::{
+	colony::Colony,
+	context::TickContext,
+	error::{Error, Result},
+	jobs::{
+		collect::choose,
+		harvest::{closest_source, harvest_fallback},
+		CreepJob, Job, JobKind,
+	},
+	market::Offer,
+	movement::move_to_object,
+	structures::CreepTarget,
+	util::room_name,
+};
+
+/// Order in which construction is done, lowest first
+fn rank(structure_type: StructureType) -> u8 {
+	match structure_type {
+		StructureType::Spawn => 0,
+		StructureType::Extension => 1,
+		StructureType::Tower => 2,
+		StructureType::Container => 3,
+		StructureType::Storage => 4,
+		StructureType::Road => 6,
+		StructureType::Wall | StructureType::Rampart => 7,
+		_ => 5,
+	}
+}
+
+/// Site to build next: the most important type first, partially built sites of that type before
+/// new ones, then the closest
+pub fn next_site(sites: &[ConstructionSite], pos: &RoomPosition) -> Option<ConstructionSite> {
+	let best = sites.iter().map(|site| rank(site.structure_type())).min()?;
+	let candidates = sites
+		.iter()
+		.filter(|site| rank(site.structure_type()) == best)
+		.collect::<Vec<_>>();
+	let started = candidates.iter().any(|site| site.progress() > 0);
+
+	let array = candidates
+		.into_iter()
+		.filter(|site| !started || site.progress() > 0)
+		.map(JsValue::from)
+		.collect::<Array>();
+	pos.find_closest_by_path(&array, None)
+		.map(JsValue::from)
+		.map(ConstructionSite::from)
+}
+
+/// A single offer while the colony has construction sites, taken by every idle builder
+pub fn offers(colony: &Colony, ctx: &TickContext) -> Vec<Offer> {
+	if ctx.construction_sites_in(&colony.name).is_empty() {
+		return Vec::new();
+	}
+	vec![Offer::new(
+		&colony.name,
+		CreepJob::Build(Default::default()),
+		JobKind::Build.priority(),
+	)]
+}
+
+/// Build construction sites in priority order, refilling from the energy stored in the room, or
+/// failing that a source
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum BuildState {
+	Entry,
+	Withdrawing(CreepTarget),
+	Harvesting(CreepTarget),
+	Building(CreepTarget),
+	Done,
+}
+
+impl Default for BuildState {
+	fn default() -> Self { Self::Entry }
+}
+
+impl Job for BuildState {
+	fn finished(&self) -> bool {
+		if let BuildState::Done = self {
+			true
+		}
+		else {
+			false
+		}
+	}
+
+	fn state_name(&self) -> &'static str {
+		match self {
+			BuildState::Entry => "Entry",
+			BuildState::Withdrawing(_) => "Withdrawing",
+			BuildState::Harvesting(_) => "Harvesting",
+			BuildState::Building(_) => "Building",
+			BuildState::Done => "Done",
+		}
+	}
+
+	fn target(&self) -> Option<&CreepTarget> {
+		match self {
+			BuildState::Withdrawing(target)
+			| BuildState::Harvesting(target)
+			| BuildState::Building(target) => Some(target),
+			_ => None,
+		}
+	}
+
+	fn preempted(&mut self) { *self = BuildState::Entry; }
+
+	fn drive(&mut self, creep: &Creep, ctx: &TickContext) -> Result<()> {
+		match self {
+			BuildState::Entry => {
+				let pos = creep.pos().ok_or(Error::Unknown)?;
+				let room = creep.room().ok_or(Error::Unknown)?;
+
+				if creep.store().get_used_capacity(Some(ResourceType::Energy)) > 0 {
+					match next_site(ctx.construction_sites_in(&room_name(&room)), &pos) {
+						Some(site) => {
+							let target = CreepTarget::from_id(&site.id().ok_or(Error::NoneFound)?);
+							*self = BuildState::Building(target);
+							creep.say(&JsString::from("Build"), false);
+						}
+						None => *self = BuildState::Done,
+					}
+					return Ok(());
+				}
+
+				if let Some(pile) = choose(ctx, creep, |pile| !pile.dropped) {
+					*self = BuildState::Withdrawing(pile.target);
+					return Ok(());
+				}
+
+				*self = BuildState::Harvesting(closest_source(creep)?);
+				Ok(())
+			}
+			BuildState::Withdrawing(target) => {
+				let target = match target.to_value() {
+					Ok(t) => t,
+					Err(_) => {
+						// Lost store - retarget
+						*self = BuildState::Entry;
+						return Ok(());
+					}
+				};
+
+				match ReturnCode::from_i8(creep.withdraw(
+					&RoomObject::from(JsValue::from(&target)),
+					ResourceType::Energy,
+					None,
+				))
+				.unwrap()
+				{
+					ReturnCode::Ok | ReturnCode::Full => {
+						*self = BuildState::Entry;
+						Ok(())
+					}
+					ReturnCode::NotInRange => move_to_object(creep, &RoomObject::from(target), 1),
+					ReturnCode::NotEnough => {
+						// Emptied by someone else - look elsewhere
+						*self = BuildState::Entry;
+						Ok(())
+					}
+					x => Err(Error::UnhandledErrorCode(x)),
+				}
+			}
+			BuildState::Harvesting(target) => {
+				if harvest_fallback(creep, target)? {
+					*self = BuildState::Entry;
+				}
+				Ok(())
+			}
+			BuildState::Building(target) => {
+				if creep.store().get_used_capacity(Some(ResourceType::Energy)) == 0 {
+					*self = BuildState::Done;
+					return Ok(());
+				}
+				let target = match target.to_value() {
+					Ok(t) => t,
+					Err(_) => {
+						// Site finished - pick the next one
+						*self = BuildState::Entry;
+						return Ok(());
+					}
+				};
+
+				match ReturnCode::from_i8(
+					creep.build(&ConstructionSite::from(JsValue::from(&target))),
+				)
+				.unwrap()
+				{
+					ReturnCode::Ok => Ok(()),
+					ReturnCode::NotInRange => move_to_object(creep, &RoomObject::from(target), 3),
+					x => Err(Error::UnhandledErrorCode(x)),
+				}
+			}
+			BuildState::Done => Ok(()),
+		}
+	}
+}
@@ -0,0 +1,219 @@
+use wasm_bindgen::prelude::*;
+
+use js_sys::JsString;
+
+use serde::{Deserialize, Serialize};
+
+use screeps::{
+	constants::{ResourceType, ReturnCode},
+	objects::{Creep, RoomObject},
+};
+
+use num_traits::cast::FromPrimitive;
+
+use crate::{
+	colony::Colony,
+	context::{EnergyPile, TickContext},
+	economy,
+	error::{Error, Result},
+	jobs::{BuildState, CreepJob, Job, JobKind},
+	market::{Load, Offer},
+	movement::move_to_object,
+	structures::{CreepState, CreepTarget},
+	util::{pickup, room_name, world_pos},
+};
+
+/// Smallest amount worth a trip, unless the creep needs less than that to fill up
+const MIN_AMOUNT: u32 = 50;
+
+/// Whether a pile is the storage of a room which is banking and has nothing to spend on, so
+/// taking from it would only carry the energy back
+fn banked(ctx: &TickContext, room: &str, pile: &EnergyPile) -> bool {
+	let room_obj = match ctx.room(room) {
+		Some(room_obj) => room_obj,
+		None => return false,
+	};
+	if !economy::policy(room).bank
+		|| room_obj.energy_available() < room_obj.energy_capacity_available()
+	{
+		return false;
+	}
+	room_obj
+		.storage()
+		.map(|storage| CreepTarget::from_id(&storage.id()) == pile.target)
+		.unwrap_or(false)
+}
+
+/// One slot per pile worth collecting, filled before creeps go to the sources
+pub fn offers(colony: &Colony, ctx: &TickContext) -> Vec<Offer> {
+	let piles = ctx
+		.energy_piles(&colony.name)
+		.iter()
+		.filter(|pile| !banked(ctx, &colony.name, pile))
+		.filter(|pile| ctx.unreserved(pile) >= MIN_AMOUNT)
+		.count();
+	if piles == 0 {
+		return Vec::new();
+	}
+	vec![Offer::new(
+		&colony.name,
+		CreepJob::Collect(Default::default()),
+		JobKind::Collect.priority(),
+	)
+	.slots(piles as u16)
+	.load(Load::Empty)]
+}
+
+/// Claim the energy creeps are already on their way to take, before any creep picks a new pile
+/// this tick. Only states which take from a pile count, as a store being delivered to is not
+/// being emptied.
+pub fn reserve_energy(ctx: &TickContext, creep_pairs: &[(Creep, CreepState)]) {
+	creep_pairs.iter().for_each(|(creep, state)| {
+		let target = match &state.job {
+			CreepJob::Collect(CollectState::Withdrawing(target))
+			| CreepJob::Collect(CollectState::PickingUp(target))
+			| CreepJob::Build(BuildState::Withdrawing(target)) => target,
+			_ => return,
+		};
+		let free = creep.store().get_free_capacity(Some(ResourceType::Energy));
+		ctx.reserve(target, free);
+	});
+}
+
+/// Pile in the creep's room giving the most energy for the distance walked
+pub fn choose(
+	ctx: &TickContext,
+	creep: &Creep,
+	filter: impl Fn(&EnergyPile) -> bool,
+) -> Option<EnergyPile> {
+	let pos = world_pos(&creep.pos()?)?;
+	let room = room_name(&creep.room()?);
+	let free = creep.store().get_free_capacity(Some(ResourceType::Energy));
+	if free == 0 {
+		return None;
+	}
+
+	ctx.energy_piles(&room)
+		.iter()
+		.filter(|pile| !banked(ctx, &room, pile) && filter(pile))
+		.map(|pile| (pile, ctx.unreserved(pile).min(free)))
+		.filter(|(_, amount)| *amount >= MIN_AMOUNT.min(free))
+		.max_by(|(a, a_amount), (b, b_amount)| {
+			let a_score = *a_amount as f64 / (pos.range(&a.pos) + 1) as f64;
+			let b_score = *b_amount as f64 / (pos.range(&b.pos) + 1) as f64;
+			a_score.partial_cmp(&b_score).unwrap()
+		})
+		.map(|(pile, amount)| {
+			ctx.reserve(&pile.target, amount);
+			pile.clone()
+		})
+}
+
+/// Take energy from stores, tombstones, ruins and the ground rather than harvesting it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum CollectState {
+	Entry,
+	Withdrawing(CreepTarget),
+	PickingUp(CreepTarget),
+	Done,
+}
+
+impl Default for CollectState {
+	fn default() -> Self { Self::Entry }
+}
+
+impl Job for CollectState {
+	fn finished(&self) -> bool {
+		if let CollectState::Done = self {
+			true
+		}
+		else {
+			false
+		}
+	}
+
+	fn state_name(&self) -> &'static str {
+		match self {
+			CollectState::Entry => "Entry",
+			CollectState::Withdrawing(_) => "Withdrawing",
+			CollectState::PickingUp(_) => "PickingUp",
+			CollectState::Done => "Done",
+		}
+	}
+
+	fn target(&self) -> Option<&CreepTarget> {
+		match self {
+			CollectState::Withdrawing(target) | CollectState::PickingUp(target) => Some(target),
+			_ => None,
+		}
+	}
+
+	fn preempted(&mut self) {
+		// Give up the reservation, the pile may be gone by the time the creep is back
+		*self = CollectState::Entry;
+	}
+
+	fn drive(&mut self, creep: &Creep, ctx: &TickContext) -> Result<()> {
+		match self {
+			CollectState::Entry => {
+				match choose(ctx, creep, |_| true) {
+					Some(pile) if pile.dropped => {
+						*self = CollectState::PickingUp(pile.target);
+						creep.say(&JsString::from("Collect"), false);
+					}
+					Some(pile) => {
+						*self = CollectState::Withdrawing(pile.target);
+						creep.say(&JsString::from("Collect"), false);
+					}
+					None => *self = CollectState::Done,
+				}
+				Ok(())
+			}
+			CollectState::Withdrawing(target) => {
+				let target = match target.to_value() {
+					Ok(t) => RoomObject::from(t),
+					Err(_) => {
+						// Decayed or emptied - retarget
+						*self = CollectState::Entry;
+						return Ok(());
+					}
+				};
+
+				match ReturnCode::from_i8(creep.withdraw(&target, ResourceType::Energy, None))
+					.unwrap()
+				{
+					ReturnCode::Ok | ReturnCode::Full => {
+						*self = CollectState::Done;
+						Ok(())
+					}
+					ReturnCode::NotInRange => move_to_object(creep, &target, 1),
+					ReturnCode::NotEnough => {
+						*self = CollectState::Entry;
+						Ok(())
+					}
+					x => Err(Error::UnhandledErrorCode(x)),
+				}
+			}
+			CollectState::PickingUp(target) => {
+				let target = match target.to_value() {
+					Ok(t) => RoomObject::from(t),
+					Err(_) => {
+						// Picked up by someone else or decayed - retarget
+						*self = CollectState::Entry;
+						return Ok(());
+					}
+				};
+
+				match pickup(creep, &target) {
+					ReturnCode::Ok | ReturnCode::Full => {
+						*self = CollectState::Done;
+						Ok(())
+					}
+					ReturnCode::NotInRange => move_to_object(creep, &target, 1),
+					x => Err(Error::UnhandledErrorCode(x)),
+				}
+			}
+			CollectState::Done => Ok(()),
+		}
+	}
+}
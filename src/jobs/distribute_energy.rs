@@ -5,21 +5,49 @@ use js_sys::{Array, JsString};
 use serde::{Deserialize, Serialize};
 
 use screeps::{
-	constants::{Part, ResourceType, ReturnCode, StructureType},
-	objects::{ConstructionSite, Creep, Flag, Room, RoomObject, Structure, StructureSpawn},
-	StructureExtension,
+	constants::{ResourceType, ReturnCode, StructureType},
+	objects::{ConstructionSite, Creep, RoomObject, Structure, StructureContainer},
 };
 
 use num_traits::cast::FromPrimitive;
 
-use std::collections::HashMap;
-
 use crate::{
+	colony::Colony,
+	context::TickContext,
+	economy,
 	error::{Error, Result},
-	jobs::Job,
-	structures::{CreepParts, CreepTarget},
+	jobs::{build::next_site, CreepJob, Job, JobKind},
+	market::{Load, Offer},
+	movement::move_to_object,
+	structures::CreepTarget,
+	util::room_name,
 };
 
+/// Priority while spawns and extensions need filling
+const REFILL_PRIORITY: u8 = 60;
+
+/// A single offer taking any number of loaded creeps, which pick their target on entry
+pub fn offers(colony: &Colony, _ctx: &TickContext) -> Vec<Offer> {
+	let room = &colony.room;
+	let priority = if room.energy_available() < room.energy_capacity_available() {
+		REFILL_PRIORITY
+	}
+	else {
+		JobKind::DistributeEnergy.priority()
+	};
+
+	let mut offer = Offer::new(
+		&colony.name,
+		CreepJob::DistributeEnergy(Default::default()),
+		priority,
+	)
+	.load(Load::Full);
+	if let Some(spawner) = colony.spawns.first() {
+		offer = offer.at(&RoomObject::from(JsValue::from(spawner.clone())));
+	}
+	vec![offer]
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum DistributeEnergyState {
 	Entry,
@@ -33,6 +61,20 @@ impl Default for DistributeEnergyState {
 	fn default() -> Self { Self::Entry }
 }
 
+/// The container feeding a room's upgraders, if it has room for more energy
+fn controller_container(ctx: &TickContext, room: &str) -> Option<StructureContainer> {
+	ctx.controller_feed(room)
+		.filter(|feed| feed.structure_type() == StructureType::Container)
+		.map(JsValue::from)
+		.map(StructureContainer::from)
+		.filter(|container| {
+			container
+				.store()
+				.get_free_capacity(Some(ResourceType::Energy))
+				> 0
+		})
+}
+
 impl Job for DistributeEnergyState {
 	fn finished(&self) -> bool {
 		if let DistributeEnergyState::Done = self {
@@ -43,58 +85,56 @@ impl Job for DistributeEnergyState {
 		}
 	}
 
-	fn min_required(&self) -> CreepParts {
-		CreepParts(
-			[(Part::Move, 1), (Part::Work, 1), (Part::Carry, 1)]
-				.iter()
-				.copied()
-				.collect::<HashMap<_, _>>(),
-		)
+	fn state_name(&self) -> &'static str {
+		match self {
+			DistributeEnergyState::Entry => "Entry",
+			DistributeEnergyState::Distributing(_) => "Distributing",
+			DistributeEnergyState::Building(_) => "Building",
+			DistributeEnergyState::Upgrading => "Upgrading",
+			DistributeEnergyState::Done => "Done",
+		}
+	}
+
+	fn target(&self) -> Option<&CreepTarget> {
+		match self {
+			DistributeEnergyState::Distributing(target)
+			| DistributeEnergyState::Building(target) => Some(target),
+			_ => None,
+		}
+	}
+
+	fn preempted(&mut self) {
+		// Targets may have been filled or finished meanwhile
+		if let DistributeEnergyState::Distributing(_) | DistributeEnergyState::Building(_) = self {
+			*self = DistributeEnergyState::Entry;
+		}
 	}
 
-	fn drive(
-		&mut self,
-		creep: &Creep,
-		construction_sites: &[ConstructionSite],
-		flags: &[Flag],
-		rooms: &[Room],
-		spawns: &[StructureSpawn],
-		structures: &[Structure],
-	) -> Result<()> {
+	fn drive(&mut self, creep: &Creep, ctx: &TickContext) -> Result<()> {
 		match self {
 			DistributeEnergyState::Entry => {
 				let pos = creep.pos().ok_or(Error::Unknown)?;
 				let room = creep.room().ok_or(Error::Unknown)?;
+				let name = room_name(&room);
 
 				// Find target using order of priorities
 
 				let energy_capacity = room.energy_capacity_available();
 				let energy_available = room.energy_available();
 
+				let construction_sites = ctx.construction_sites_in(&name);
+
 				if energy_available < energy_capacity {
 					// Distribute to spawner or extensions
 
-					let spawns = spawns
-						.iter()
-						.filter(|x| x.store().get_free_capacity(Some(ResourceType::Energy)) > 0)
-						.collect::<Vec<_>>();
-
-					let extensions = structures
+					let sinks = ctx
+						.energy_sinks(&name)
 						.iter()
-						.filter(|x| x.structure_type() == StructureType::Extension)
-						.map(JsValue::from)
-						.map(StructureExtension::from)
-						.filter(|x| x.store().get_free_capacity(Some(ResourceType::Energy)) > 0)
-						.collect::<Vec<_>>();
-
-					let combined = spawns
-						.into_iter()
 						.map(JsValue::from)
-						.chain(extensions.into_iter().map(JsValue::from))
 						.collect::<Array>();
 
 					let closest = pos
-						.find_closest_by_path(&combined, None)
+						.find_closest_by_path(&sinks, None)
 						.map(JsValue::from)
 						.map(Structure::from)
 						.ok_or(Error::NoneFound)?;
@@ -109,16 +149,7 @@ impl Job for DistributeEnergyState {
 				else if !construction_sites.is_empty() {
 					// Help build construction sites
 
-					let array = construction_sites
-						.iter()
-						.map(JsValue::from)
-						.collect::<Array>();
-
-					let closest = pos
-						.find_closest_by_path(&array, None)
-						.map(JsValue::from)
-						.map(ConstructionSite::from)
-						.ok_or(Error::NoneFound)?;
+					let closest = next_site(construction_sites, &pos).ok_or(Error::NoneFound)?;
 
 					let target = CreepTarget::from_id(&closest.id().ok_or(Error::NoneFound)?);
 
@@ -127,6 +158,29 @@ impl Job for DistributeEnergyState {
 
 					Ok(())
 				}
+				else if let Some(storage) = room.storage().filter(|storage| {
+					economy::policy(&name).bank
+						&& storage
+							.store()
+							.get_free_capacity(Some(ResourceType::Energy))
+							> 0
+				}) {
+					// Saving up - bank what the spawns do not need
+
+					*self =
+						DistributeEnergyState::Distributing(CreepTarget::from_id(&storage.id()));
+					creep.say(&JsString::from("Bank"), false);
+
+					Ok(())
+				}
+				else if let Some(feed) = controller_container(ctx, &name) {
+					// Keep the upgraders supplied
+
+					*self = DistributeEnergyState::Distributing(CreepTarget::from_id(&feed.id()));
+					creep.say(&JsString::from("Distribute"), false);
+
+					Ok(())
+				}
 				else if room.controller().is_some() {
 					// Upgrade the controller
 
@@ -164,10 +218,7 @@ impl Job for DistributeEnergyState {
 				.unwrap()
 				{
 					ReturnCode::Ok => Ok(()),
-					ReturnCode::NotInRange => {
-						creep.move_to(&target, None);
-						Ok(())
-					}
+					ReturnCode::NotInRange => move_to_object(creep, &RoomObject::from(target), 1),
 					ReturnCode::Full => {
 						// Full target - retarget
 						*self = DistributeEnergyState::Entry;
@@ -198,10 +249,7 @@ impl Job for DistributeEnergyState {
 				.unwrap()
 				{
 					ReturnCode::Ok => Ok(()),
-					ReturnCode::NotInRange => {
-						creep.move_to(&target, None);
-						Ok(())
-					}
+					ReturnCode::NotInRange => move_to_object(creep, &RoomObject::from(target), 3),
 					x => Err(Error::UnhandledErrorCode(x)),
 				}
 			}
@@ -218,10 +266,7 @@ impl Job for DistributeEnergyState {
 				let controller = room.controller().unwrap();
 				match ReturnCode::from_i8(creep.upgrade_controller(&controller)).unwrap() {
 					ReturnCode::Ok => Ok(()),
-					ReturnCode::NotInRange => {
-						creep.move_to(&controller, None);
-						Ok(())
-					}
+					ReturnCode::NotInRange => move_to_object(creep, &controller, 3),
 					x => Err(Error::UnhandledErrorCode(x)),
 				}
 			}
@@ -0,0 +1,102 @@
+use js_sys::JsString;
+
+use serde::{Deserialize, Serialize};
+
+use screeps::objects::Creep;
+
+use crate::{
+	context::TickContext,
+	coords::WorldPos,
+	error::{Error, Result},
+	intel,
+	jobs::Job,
+	movement::move_to,
+	structures::CreepTarget,
+	travel::find_flee_path,
+	util::{room_position, world_pos},
+};
+
+/// Tiles kept between the creep and the reach of a threat
+const MARGIN: u32 = 2;
+
+/// Retreat out of reach of hostile creeps. Only taken through an interrupt.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum FleeState {
+	Entry,
+	Fleeing(WorldPos),
+	Done,
+}
+
+impl Default for FleeState {
+	fn default() -> Self { Self::Entry }
+}
+
+/// Threats the creep is within reach of, widened by the safety margin
+fn nearby_threats(creep: &Creep, pos: WorldPos) -> Vec<(WorldPos, u32)> {
+	creep
+		.room()
+		.map(|room| intel::threats(&room))
+		.unwrap_or_default()
+		.into_iter()
+		.map(|(threat, range)| (threat, range + MARGIN))
+		.filter(|(threat, range)| threat.range(&pos) <= *range)
+		.collect()
+}
+
+impl Job for FleeState {
+	fn finished(&self) -> bool {
+		if let FleeState::Done = self {
+			true
+		}
+		else {
+			false
+		}
+	}
+
+	fn state_name(&self) -> &'static str {
+		match self {
+			FleeState::Entry => "Entry",
+			FleeState::Fleeing(_) => "Fleeing",
+			FleeState::Done => "Done",
+		}
+	}
+
+	fn target(&self) -> Option<&CreepTarget> { None }
+
+	fn resumable(&self) -> bool { false }
+
+	fn drive(&mut self, creep: &Creep, _ctx: &TickContext) -> Result<()> {
+		let pos = creep
+			.pos()
+			.as_ref()
+			.and_then(world_pos)
+			.ok_or(Error::Unknown)?;
+		let threats = nearby_threats(creep, pos);
+		if threats.is_empty() {
+			*self = FleeState::Done;
+			return Ok(());
+		}
+
+		match self {
+			FleeState::Entry => {
+				match find_flee_path(pos, &threats)?.last() {
+					Some(destination) => {
+						*self = FleeState::Fleeing(*destination);
+						creep.say(&JsString::from("Flee"), false);
+					}
+					None => *self = FleeState::Done,
+				}
+				Ok(())
+			}
+			FleeState::Fleeing(destination) => {
+				if *destination == pos {
+					// Still in reach at the end of the path - look further
+					*self = FleeState::Entry;
+					return Ok(());
+				}
+				move_to(creep, &room_position(*destination), 0)
+			}
+			FleeState::Done => Ok(()),
+		}
+	}
+}
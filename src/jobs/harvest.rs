@@ -5,21 +5,87 @@ use js_sys::JsString;
 use serde::{Deserialize, Serialize};
 
 use screeps::{
-	constants::{Find, Part, ResourceType, ReturnCode},
-	objects::{ConstructionSite, Creep, Flag, Room, RoomObject, Structure, StructureSpawn},
+	constants::{Find, ResourceType, ReturnCode},
+	objects::{Creep, RoomObject},
 	Source,
 };
 
 use num_traits::cast::FromPrimitive;
 
-use std::collections::HashMap;
-
 use crate::{
+	colony::Colony,
+	context::TickContext,
+	cost_matrix::terrain,
 	error::{Error, Result},
-	jobs::Job,
-	structures::{CreepParts, CreepTarget},
+	jobs::{CreepJob, Job, JobKind},
+	links::source_link_at,
+	market::{Load, Offer},
+	movement::move_to_object,
+	pathfinding::Terrain,
+	structures::CreepTarget,
+	util::{room_name, world_pos},
 };
 
+/// One offer per source, with a slot for every tile a harvester can stand on
+pub fn offers(colony: &Colony, ctx: &TickContext) -> Vec<Offer> {
+	let terrain = terrain(&colony.name);
+	ctx.sources_in(&colony.name)
+		.iter()
+		.filter_map(|source| {
+			let pos = source.pos()?;
+			let (x, y) = (pos.x() as i32, pos.y() as i32);
+			let open = (-1..=1)
+				.flat_map(|dx| (-1..=1).map(move |dy| (dx, dy)))
+				// The source itself can't be stood on, whatever the terrain under it
+				.filter(|&offset| offset != (0, 0))
+				.map(|(dx, dy)| (x + dx, y + dy))
+				.filter(|&(x, y)| (0..50).contains(&x) && (0..50).contains(&y))
+				.filter(|&(x, y)| terrain.get(x as u8, y as u8) != Terrain::Wall)
+				.count();
+
+			let job =
+				CreepJob::Harvest(HarvestState::Harvesting(CreepTarget::from_id(&source.id())));
+			Some(
+				Offer::new(&colony.name, job, JobKind::Harvest.priority())
+					.at(&RoomObject::from(JsValue::from(source.clone())))
+					.slots(open as u16)
+					.load(Load::Empty),
+			)
+		})
+		.collect()
+}
+
+/// Source closest to a creep by path
+pub fn closest_source(creep: &Creep) -> Result<CreepTarget> {
+	let pos = creep.pos().ok_or(Error::Unknown)?;
+	let closest = pos
+		.find_closest_by_path(&JsValue::from(Find::Sources as i32), None)
+		.ok_or(Error::NoneFound)?;
+	Ok(CreepTarget::from_id(
+		&Source::from(JsValue::from(closest)).id(),
+	))
+}
+
+/// Fill up at a source like a worker, for dedicated builds with nothing to draw from yet.
+/// `Ok(true)` once the creep is full, or the source is gone or empty and the job should look
+/// again.
+pub fn harvest_fallback(creep: &Creep, source: &CreepTarget) -> Result<bool> {
+	if creep.store().get_free_capacity(Some(ResourceType::Energy)) == 0 {
+		return Ok(true);
+	}
+	let source = match source.to_value() {
+		Ok(t) => RoomObject::from(t),
+		Err(_) => return Ok(true),
+	};
+
+	match ReturnCode::from_i8(creep.harvest(&source)).unwrap() {
+		ReturnCode::Ok | ReturnCode::Busy => Ok(false),
+		ReturnCode::NotInRange => move_to_object(creep, &source, 1).map(|_| false),
+		ReturnCode::NotEnough => Ok(true),
+		x => Err(Error::UnhandledErrorCode(x)),
+	}
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum HarvestState {
 	Entry,
@@ -41,38 +107,30 @@ impl Job for HarvestState {
 		}
 	}
 
-	fn min_required(&self) -> CreepParts {
-		CreepParts(
-			[(Part::Move, 1), (Part::Work, 1), (Part::Carry, 1)]
-				.iter()
-				.copied()
-				.collect::<HashMap<_, _>>(),
-		)
+	fn state_name(&self) -> &'static str {
+		match self {
+			HarvestState::Entry => "Entry",
+			HarvestState::Harvesting(_) => "Harvesting",
+			HarvestState::Done => "Done",
+		}
 	}
 
-	fn drive(
-		&mut self,
-		creep: &Creep,
-		construction_sites: &[ConstructionSite],
-		flags: &[Flag],
-		rooms: &[Room],
-		spawns: &[StructureSpawn],
-		structures: &[Structure],
-	) -> Result<()> {
+	fn target(&self) -> Option<&CreepTarget> {
 		match self {
-			HarvestState::Entry => {
-				// Find target
-				let pos = creep.pos().ok_or(Error::Unknown)?;
-
-				let closest = pos
-					.find_closest_by_path(&JsValue::from(Find::Sources as i32), None)
-					.ok_or(Error::NoneFound)?;
-
-				let closest = Source::from(JsValue::from(closest));
+			HarvestState::Harvesting(target) => Some(target),
+			_ => None,
+		}
+	}
 
-				let target = CreepTarget::from_id(&closest.id());
+	fn preempted(&mut self) {
+		// Give up the source so the market can fill it, picking the closest again on resume
+		*self = HarvestState::Entry;
+	}
 
-				*self = HarvestState::Harvesting(target);
+	fn drive(&mut self, creep: &Creep, ctx: &TickContext) -> Result<()> {
+		match self {
+			HarvestState::Entry => {
+				*self = HarvestState::Harvesting(closest_source(creep)?);
 				creep.say(&JsString::from("Harvest"), false);
 				Ok(())
 			}
@@ -80,6 +138,28 @@ impl Job for HarvestState {
 				let store = creep.store();
 				let energy_free_capacity = store.get_free_capacity(Some(ResourceType::Energy));
 				if energy_free_capacity == 0 {
+					// Hand the energy to the link network and keep harvesting if we can
+					let link = match (creep.pos(), creep.room()) {
+						(Some(pos), Some(room)) => world_pos(&pos)
+							.and_then(|pos| source_link_at(ctx, &room_name(&room), pos)),
+						_ => None,
+					};
+					let link = link.filter(|link| {
+						link.store().get_free_capacity(Some(ResourceType::Energy)) > 0
+					});
+					if let Some(link) = link {
+						let link = RoomObject::from(JsValue::from(link));
+						return match ReturnCode::from_i8(creep.transfer(
+							&link,
+							ResourceType::Energy,
+							None,
+						))
+						.unwrap()
+						{
+							ReturnCode::Ok => Ok(()),
+							x => Err(Error::UnhandledErrorCode(x)),
+						};
+					}
 					*self = HarvestState::Done;
 					return Ok(());
 				}
@@ -95,10 +175,7 @@ impl Job for HarvestState {
 					.unwrap()
 				{
 					ReturnCode::Ok => Ok(()),
-					ReturnCode::NotInRange => {
-						creep.move_to(&target, None);
-						Ok(())
-					}
+					ReturnCode::NotInRange => move_to_object(creep, &RoomObject::from(target), 1),
 					ReturnCode::Busy => Ok(()),
 					ReturnCode::NotEnough => {
 						*self = HarvestState::Entry;
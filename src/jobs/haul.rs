@@ -0,0 +1,229 @@
+use wasm_bindgen::prelude::*;
+
+use js_sys::{JsString, Object};
+
+use serde::{Deserialize, Serialize};
+
+use screeps::{
+	constants::{ResourceType, ReturnCode, StructureType},
+	objects::{Creep, RoomObject, StructureContainer, StructureTerminal},
+};
+
+use crate::{
+	colony::Colony,
+	context::TickContext,
+	error::{Error, Result},
+	jobs::{mine::container, CreepJob, Job, JobKind},
+	market::{Load, Offer},
+	movement::move_to_object,
+	structures::CreepTarget,
+	util::{room_name, transfer_resource, withdraw_resource},
+};
+
+/// Minerals in the mining container worth a trip
+const MIN_AMOUNT: u32 = 200;
+/// Resources kept in the terminal for trading before the rest goes to the storage
+const TERMINAL_STOCK: u32 = 50_000;
+
+/// Resources other than energy held in a store
+pub fn minerals(store: &JsValue) -> Vec<String> {
+	Object::keys(store.unchecked_ref())
+		.iter()
+		.filter_map(|resource| resource.as_string())
+		.filter(|resource| resource != "energy")
+		.collect()
+}
+
+/// Where minerals go in a room: the terminal until it holds its trading stock, then the storage.
+/// The stock only counts minerals, as the terminal also holds the energy trading costs.
+pub fn destination(ctx: &TickContext, room: &str) -> Option<CreepTarget> {
+	let terminal = ctx
+		.structures_in(room, StructureType::Terminal)
+		.first()
+		.map(JsValue::from)
+		.map(StructureTerminal::from)
+		.filter(|terminal| {
+			let store = terminal.store();
+			let stock =
+				store.get_used_capacity(None) - store.get_used_capacity(Some(ResourceType::Energy));
+			stock < TERMINAL_STOCK && store.get_free_capacity(None) > 0
+		})
+		.map(|terminal| CreepTarget::from_id(&terminal.id()));
+	terminal.or_else(|| {
+		ctx.room(room)
+			.and_then(|room| room.storage())
+			.filter(|storage| storage.store().get_free_capacity(None) > 0)
+			.map(|storage| CreepTarget::from_id(&storage.id()))
+	})
+}
+
+/// Where a creep holding minerals unloads: the destination, or back into the mining container
+/// while the terminal and storage are both full
+pub fn unload_target(ctx: &TickContext, room: &str) -> Option<CreepTarget> {
+	destination(ctx, room).or_else(|| {
+		container(ctx, room)
+			.filter(|container| container.store().get_free_capacity(None) > 0)
+			.map(|container| CreepTarget::from_id(&container.id()))
+	})
+}
+
+/// Swap a store which is full or gone for the next one with room, keeping it while there is none
+fn retarget(creep: &Creep, ctx: &TickContext, target: &mut CreepTarget) -> Result<bool> {
+	let room = room_name(&creep.room().ok_or(Error::Unknown)?);
+	if let Some(next) = unload_target(ctx, &room) {
+		*target = next;
+	}
+	Ok(false)
+}
+
+/// Put the minerals a creep carries into a store, one resource per tick, moving on to another
+/// store when it fills up. `Ok(true)` once the creep has nothing left to hand over.
+pub fn deliver(creep: &Creep, ctx: &TickContext, target: &mut CreepTarget) -> Result<bool> {
+	let resource = match minerals(&JsValue::from(creep.store())).into_iter().next() {
+		Some(resource) => resource,
+		None => return Ok(true),
+	};
+	let store = match target.to_value() {
+		Ok(t) => RoomObject::from(t),
+		Err(_) => return retarget(creep, ctx, target),
+	};
+
+	match transfer_resource(creep, &store, &resource, None) {
+		ReturnCode::Ok => Ok(false),
+		ReturnCode::NotInRange => move_to_object(creep, &store, 1).map(|_| false),
+		ReturnCode::Full => retarget(creep, ctx, target),
+		x => Err(Error::UnhandledErrorCode(x)),
+	}
+}
+
+fn container_minerals(container: &StructureContainer) -> u32 {
+	let store = container.store();
+	store.get_used_capacity(None) - store.get_used_capacity(Some(ResourceType::Energy))
+}
+
+/// One slot while the mining container has a load of minerals and there is somewhere to put them
+pub fn offers(colony: &Colony, ctx: &TickContext) -> Vec<Offer> {
+	let container = match container(ctx, &colony.name) {
+		Some(container) => container,
+		None => return Vec::new(),
+	};
+	if container_minerals(&container) < MIN_AMOUNT || destination(ctx, &colony.name).is_none() {
+		return Vec::new();
+	}
+	vec![Offer::new(
+		&colony.name,
+		CreepJob::Haul(Default::default()),
+		JobKind::Haul.priority(),
+	)
+	.at(&RoomObject::from(JsValue::from(container)))
+	.slots(1)
+	.load(Load::Empty)]
+}
+
+/// Carry minerals from the mining container to the terminal or storage
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum HaulState {
+	Entry,
+	Withdrawing(CreepTarget),
+	Delivering(CreepTarget),
+	Done,
+}
+
+impl Default for HaulState {
+	fn default() -> Self { Self::Entry }
+}
+
+impl Job for HaulState {
+	fn finished(&self) -> bool {
+		if let HaulState::Done = self {
+			true
+		}
+		else {
+			false
+		}
+	}
+
+	fn state_name(&self) -> &'static str {
+		match self {
+			HaulState::Entry => "Entry",
+			HaulState::Withdrawing(_) => "Withdrawing",
+			HaulState::Delivering(_) => "Delivering",
+			HaulState::Done => "Done",
+		}
+	}
+
+	fn target(&self) -> Option<&CreepTarget> {
+		match self {
+			HaulState::Withdrawing(target) | HaulState::Delivering(target) => Some(target),
+			_ => None,
+		}
+	}
+
+	fn preempted(&mut self) { *self = HaulState::Entry; }
+
+	fn drive(&mut self, creep: &Creep, ctx: &TickContext) -> Result<()> {
+		match self {
+			HaulState::Entry => {
+				let room = room_name(&creep.room().ok_or(Error::Unknown)?);
+				let store = creep.store();
+				let carrying = !minerals(&JsValue::from(store.clone())).is_empty();
+				// Only take more while there is somewhere to bring it
+				let container = container(ctx, &room)
+					.filter(|container| container_minerals(container) > 0)
+					.filter(|_| destination(ctx, &room).is_some());
+
+				*self = match container {
+					Some(container) if store.get_free_capacity(None) > 0 => {
+						creep.say(&JsString::from("Haul"), false);
+						HaulState::Withdrawing(CreepTarget::from_id(&container.id()))
+					}
+					_ if carrying => match unload_target(ctx, &room) {
+						Some(target) => HaulState::Delivering(target),
+						// Every store is full - hold on to the load until one has room
+						None => HaulState::Entry,
+					},
+					_ => HaulState::Done,
+				};
+				Ok(())
+			}
+			HaulState::Withdrawing(target) => {
+				let target = match target.to_value() {
+					Ok(t) => RoomObject::from(t),
+					Err(_) => {
+						*self = HaulState::Entry;
+						return Ok(());
+					}
+				};
+				let resource = match minerals(&JsValue::from(
+					StructureContainer::from(JsValue::from(&target)).store(),
+				))
+				.into_iter()
+				.next()
+				{
+					Some(resource) => resource,
+					None => {
+						// Emptied - take what we have
+						*self = HaulState::Entry;
+						return Ok(());
+					}
+				};
+
+				match withdraw_resource(creep, &target, &resource, None) {
+					ReturnCode::Ok | ReturnCode::Full | ReturnCode::NotEnough => {
+						*self = HaulState::Entry;
+						Ok(())
+					}
+					ReturnCode::NotInRange => move_to_object(creep, &target, 1),
+					x => Err(Error::UnhandledErrorCode(x)),
+				}
+			}
+			HaulState::Delivering(target) => {
+				if deliver(creep, ctx, target)? {
+					*self = HaulState::Done;
+				}
+				Ok(())
+			}
+			HaulState::Done => Ok(()),
+		}
+	}
+}
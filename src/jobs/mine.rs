@@ -0,0 +1,202 @@
+use wasm_bindgen::prelude::*;
+
+use js_sys::JsString;
+
+use serde::{Deserialize, Serialize};
+
+use screeps::{
+	constants::{Find, ReturnCode, StructureType},
+	objects::{Creep, RoomObject, Structure, StructureContainer},
+	Mineral,
+};
+
+use num_traits::cast::FromPrimitive;
+
+use crate::{
+	colony::Colony,
+	context::TickContext,
+	error::{Error, Result},
+	jobs::{
+		haul::{deliver, destination, minerals, unload_target},
+		CreepJob, Job, JobKind,
+	},
+	market::Offer,
+	movement::move_to_object,
+	structures::CreepTarget,
+	util::{cooldown, mineral_amount, room_find, room_name, world_pos},
+};
+
+/// Controller level from which a room can have an extractor
+const MIN_LEVEL: u8 = 6;
+
+fn extractor(ctx: &TickContext, room: &str) -> Option<&Structure> {
+	ctx.structures_in(room, StructureType::Extractor).first()
+}
+
+/// The room's mineral while it has an extractor and something left to mine. A depleted mineral
+/// is regenerating, and is left alone until it has refilled.
+pub fn mineable<'a>(ctx: &'a TickContext, colony: &Colony) -> Option<&'a Mineral> {
+	if colony.level() < MIN_LEVEL {
+		return None;
+	}
+	extractor(ctx, &colony.name)?;
+	ctx.mineral(&colony.name)
+		.filter(|mineral| mineral_amount(mineral) > 0)
+}
+
+/// Container next to a room's mineral, which miners fill and haulers empty
+pub fn container(ctx: &TickContext, room: &str) -> Option<StructureContainer> {
+	let mineral = world_pos(&ctx.mineral(room)?.pos()?)?;
+	room_find(ctx.room(room)?, Find::Structures)
+		.iter()
+		.map(Structure::from)
+		.filter(|structure| structure.structure_type() == StructureType::Container)
+		.find(|structure| {
+			structure
+				.pos()
+				.as_ref()
+				.and_then(world_pos)
+				.map(|pos| pos.range(&mineral) <= 1)
+				.unwrap_or(false)
+		})
+		.map(JsValue::from)
+		.map(StructureContainer::from)
+}
+
+/// A single slot at the mineral, taken by the dedicated miner
+pub fn offers(colony: &Colony, ctx: &TickContext) -> Vec<Offer> {
+	let mineral = match mineable(ctx, colony) {
+		Some(mineral) => mineral,
+		None => return Vec::new(),
+	};
+	let job = CreepJob::Mine(MineState::Mining(CreepTarget::from_id(&mineral.id())));
+	vec![Offer::new(&colony.name, job, JobKind::Mine.priority())
+		.at(&RoomObject::from(JsValue::from(mineral.clone())))
+		.slots(1)]
+}
+
+/// Harvest the room's mineral through its extractor, dropping the minerals into the container
+/// next to it, or carrying them to the terminal or storage if there is none
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum MineState {
+	Entry,
+	Mining(CreepTarget),
+	Delivering(CreepTarget),
+	Done,
+}
+
+impl Default for MineState {
+	fn default() -> Self { Self::Entry }
+}
+
+impl Job for MineState {
+	fn finished(&self) -> bool {
+		if let MineState::Done = self {
+			true
+		}
+		else {
+			false
+		}
+	}
+
+	fn state_name(&self) -> &'static str {
+		match self {
+			MineState::Entry => "Entry",
+			MineState::Mining(_) => "Mining",
+			MineState::Delivering(_) => "Delivering",
+			MineState::Done => "Done",
+		}
+	}
+
+	fn target(&self) -> Option<&CreepTarget> {
+		match self {
+			MineState::Mining(target) | MineState::Delivering(target) => Some(target),
+			_ => None,
+		}
+	}
+
+	fn drive(&mut self, creep: &Creep, ctx: &TickContext) -> Result<()> {
+		match self {
+			MineState::Entry => {
+				let room = room_name(&creep.room().ok_or(Error::Unknown)?);
+				*self = match ctx
+					.mineral(&room)
+					.filter(|mineral| mineral_amount(mineral) > 0)
+				{
+					Some(mineral) if extractor(ctx, &room).is_some() => {
+						creep.say(&JsString::from("Mine"), false);
+						MineState::Mining(CreepTarget::from_id(&mineral.id()))
+					}
+					// Nothing left to mine - bring what we hold away before finishing
+					_ if !minerals(&JsValue::from(creep.store())).is_empty() => {
+						match unload_target(ctx, &room) {
+							Some(target) => MineState::Delivering(target),
+							None => MineState::Entry,
+						}
+					}
+					_ => MineState::Done,
+				};
+				Ok(())
+			}
+			MineState::Mining(target) => {
+				let room = room_name(&creep.room().ok_or(Error::Unknown)?);
+
+				if creep.store().get_free_capacity(None) == 0 {
+					// Into the container next to the mineral, or when there is none or it is full
+					// carry the load away ourselves. With nowhere to put it, wait with it.
+					let target = container(ctx, &room)
+						.filter(|container| container.store().get_free_capacity(None) > 0)
+						.map(|container| CreepTarget::from_id(&container.id()))
+						.or_else(|| destination(ctx, &room));
+					if let Some(target) = target {
+						*self = MineState::Delivering(target);
+					}
+					return Ok(());
+				}
+
+				let target = match target.to_value() {
+					Ok(t) => RoomObject::from(t),
+					Err(_) => {
+						*self = MineState::Entry;
+						return Ok(());
+					}
+				};
+				let in_range = match (creep.pos(), target.pos()) {
+					(Some(pos), Some(mineral)) => match (world_pos(&pos), world_pos(&mineral)) {
+						(Some(pos), Some(mineral)) => pos.range(&mineral) <= 1,
+						_ => false,
+					},
+					_ => false,
+				};
+				if !in_range {
+					return move_to_object(creep, &target, 1);
+				}
+				// The extractor can only be used every few ticks, wait next to the mineral
+				let cooling = extractor(ctx, &room)
+					.map(|extractor| cooldown(extractor) > 0)
+					.unwrap_or(false);
+				if cooling {
+					return Ok(());
+				}
+
+				match ReturnCode::from_i8(creep.harvest(&target)).unwrap() {
+					ReturnCode::Ok | ReturnCode::Tired => Ok(()),
+					ReturnCode::NotInRange => move_to_object(creep, &target, 1),
+					ReturnCode::NotEnough | ReturnCode::NotFound => {
+						// Depleted, or the extractor is gone
+						*self = MineState::Entry;
+						Ok(())
+					}
+					x => Err(Error::UnhandledErrorCode(x)),
+				}
+			}
+			MineState::Delivering(target) => {
+				if deliver(creep, ctx, target)? {
+					*self = MineState::Entry;
+				}
+				Ok(())
+			}
+			MineState::Done => Ok(()),
+		}
+	}
+}
@@ -0,0 +1,94 @@
+use wasm_bindgen::prelude::*;
+
+use js_sys::JsString;
+
+use serde::{Deserialize, Serialize};
+
+use screeps::{
+	constants::ReturnCode,
+	objects::{Creep, RoomObject, StructureSpawn},
+};
+
+use crate::{
+	context::TickContext,
+	error::{Error, Result},
+	jobs::{renew::closest_spawn, Job},
+	movement::move_to_object,
+	structures::CreepTarget,
+	util::recycle_creep,
+};
+
+/// Walk to a spawn to be recycled, returning part of the creep's cost. Only assigned by the
+/// lifecycle pass, to creeps whose build is obsolete.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum RecycleState {
+	Entry,
+	Recycling(CreepTarget),
+	Done,
+}
+
+impl Default for RecycleState {
+	fn default() -> Self { Self::Entry }
+}
+
+impl Job for RecycleState {
+	fn finished(&self) -> bool {
+		if let RecycleState::Done = self {
+			true
+		}
+		else {
+			false
+		}
+	}
+
+	fn state_name(&self) -> &'static str {
+		match self {
+			RecycleState::Entry => "Entry",
+			RecycleState::Recycling(_) => "Recycling",
+			RecycleState::Done => "Done",
+		}
+	}
+
+	fn target(&self) -> Option<&CreepTarget> {
+		match self {
+			RecycleState::Recycling(target) => Some(target),
+			_ => None,
+		}
+	}
+
+	fn resumable(&self) -> bool { false }
+
+	fn drive(&mut self, creep: &Creep, ctx: &TickContext) -> Result<()> {
+		match self {
+			RecycleState::Entry => {
+				match closest_spawn(creep, ctx)? {
+					Some(spawner) => {
+						*self = RecycleState::Recycling(CreepTarget::from_id(&spawner.id()));
+						creep.say(&JsString::from("Recycle"), false);
+					}
+					None => *self = RecycleState::Done,
+				}
+				Ok(())
+			}
+			RecycleState::Recycling(target) => {
+				let spawner = match target.to_value() {
+					Ok(t) => StructureSpawn::from(t),
+					Err(_) => {
+						// Lost spawn - retarget
+						*self = RecycleState::Entry;
+						return Ok(());
+					}
+				};
+
+				match recycle_creep(&spawner, creep) {
+					ReturnCode::Ok => Ok(()),
+					ReturnCode::NotInRange => {
+						move_to_object(creep, &RoomObject::from(JsValue::from(spawner)), 1)
+					}
+					x => Err(Error::UnhandledErrorCode(x)),
+				}
+			}
+			RecycleState::Done => Ok(()),
+		}
+	}
+}
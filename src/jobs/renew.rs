@@ -0,0 +1,119 @@
+use wasm_bindgen::prelude::*;
+
+use js_sys::{Array, JsString};
+
+use serde::{Deserialize, Serialize};
+
+use screeps::{
+	constants::ReturnCode,
+	objects::{Creep, RoomObject, StructureSpawn},
+	Game,
+};
+
+use crate::{
+	context::TickContext,
+	error::{Error, Result},
+	jobs::Job,
+	movement::move_to_object,
+	structures::CreepTarget,
+	util::{renew_creep, room_name},
+};
+
+/// Stand next to a spawn while it extends the creep's life. Only assigned by the lifecycle pass.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum RenewState {
+	Entry,
+	Renewing(CreepTarget),
+	Done,
+	/// The spawn was busy or short of energy on this tick, so the creep went back to work
+	Refused(u32),
+}
+
+impl Default for RenewState {
+	fn default() -> Self { Self::Entry }
+}
+
+/// Closest spawn in the creep's room, which renew and recycle both need to stand next to
+pub fn closest_spawn(creep: &Creep, ctx: &TickContext) -> Result<Option<StructureSpawn>> {
+	let pos = creep.pos().ok_or(Error::Unknown)?;
+	let room = creep.room().ok_or(Error::Unknown)?;
+
+	let spawns = ctx
+		.spawns_in(&room_name(&room))
+		.iter()
+		.map(JsValue::from)
+		.collect::<Array>();
+	if spawns.length() == 0 {
+		return Ok(None);
+	}
+
+	Ok(pos
+		.find_closest_by_path(&spawns, None)
+		.map(JsValue::from)
+		.map(StructureSpawn::from))
+}
+
+impl Job for RenewState {
+	fn finished(&self) -> bool { matches!(self, RenewState::Done | RenewState::Refused(_)) }
+
+	fn state_name(&self) -> &'static str {
+		match self {
+			RenewState::Entry => "Entry",
+			RenewState::Renewing(_) => "Renewing",
+			RenewState::Done => "Done",
+			RenewState::Refused(_) => "Refused",
+		}
+	}
+
+	fn target(&self) -> Option<&CreepTarget> {
+		match self {
+			RenewState::Renewing(target) => Some(target),
+			_ => None,
+		}
+	}
+
+	fn resumable(&self) -> bool { false }
+
+	fn drive(&mut self, creep: &Creep, ctx: &TickContext) -> Result<()> {
+		match self {
+			RenewState::Entry => {
+				match closest_spawn(creep, ctx)? {
+					Some(spawner) => {
+						*self = RenewState::Renewing(CreepTarget::from_id(&spawner.id()));
+						creep.say(&JsString::from("Renew"), false);
+					}
+					None => *self = RenewState::Done,
+				}
+				Ok(())
+			}
+			RenewState::Renewing(target) => {
+				let spawner = match target.to_value() {
+					Ok(t) => StructureSpawn::from(t),
+					Err(_) => {
+						// Lost spawn - retarget
+						*self = RenewState::Entry;
+						return Ok(());
+					}
+				};
+
+				match renew_creep(&spawner, creep) {
+					ReturnCode::Ok => Ok(()),
+					ReturnCode::NotInRange => {
+						move_to_object(creep, &RoomObject::from(JsValue::from(spawner)), 1)
+					}
+					ReturnCode::Full => {
+						*self = RenewState::Done;
+						Ok(())
+					}
+					// The spawn is needed for something else - back to work for a while
+					ReturnCode::Busy | ReturnCode::NotEnough => {
+						*self = RenewState::Refused(Game::time());
+						Ok(())
+					}
+					x => Err(Error::UnhandledErrorCode(x)),
+				}
+			}
+			RenewState::Done | RenewState::Refused(_) => Ok(()),
+		}
+	}
+}
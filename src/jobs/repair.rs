@@ -0,0 +1,158 @@
+use wasm_bindgen::prelude::*;
+
+use js_sys::JsString;
+
+use serde::{Deserialize, Serialize};
+
+use screeps::{
+	constants::{Find, ResourceType, ReturnCode, StructureType},
+	objects::{Creep, Room, RoomObject, Structure},
+};
+
+use crate::{
+	colony::Colony,
+	context::TickContext,
+	economy,
+	error::{Error, Result},
+	jobs::{CreepJob, Job, JobKind},
+	market::{Load, Offer},
+	movement::move_to_object,
+	structures::CreepTarget,
+	util::{hits, hits_max, repair, room_find, room_name},
+};
+
+/// Creeps repairing at once in a colony, so refilling and building are not starved
+const MAX_REPAIRERS: u16 = 2;
+
+/// Hits a structure is repaired up to. Walls and ramparts follow the room's spending policy, and
+/// anything else is repaired once it has lost half its hits.
+fn wanted_hits(structure: &Structure, wall_hits: u32) -> u32 {
+	match structure.structure_type() {
+		StructureType::Wall | StructureType::Rampart => wall_hits.min(hits_max(structure)),
+		_ => hits_max(structure),
+	}
+}
+
+fn needs_repair(structure: &Structure, wall_hits: u32) -> bool {
+	let hits = hits(structure);
+	match structure.structure_type() {
+		StructureType::Wall | StructureType::Rampart => hits < wanted_hits(structure, wall_hits),
+		_ => hits < hits_max(structure) / 2,
+	}
+}
+
+/// Structures in a room worth sending a creep to repair
+fn damaged(room: &Room) -> Vec<Structure> {
+	let wall_hits = economy::policy(&room_name(room)).wall_hits;
+	room_find(room, Find::Structures)
+		.iter()
+		.map(Structure::from)
+		.filter(|structure| structure.structure_type() != StructureType::Controller)
+		.filter(|structure| needs_repair(structure, wall_hits))
+		.collect()
+}
+
+/// A few slots while anything in the colony needs repairing, taken by loaded creeps
+pub fn offers(colony: &Colony, _ctx: &TickContext) -> Vec<Offer> {
+	let count = damaged(&colony.room).len();
+	if count == 0 {
+		return Vec::new();
+	}
+	vec![Offer::new(
+		&colony.name,
+		CreepJob::Repair(Default::default()),
+		JobKind::Repair.priority(),
+	)
+	.slots((count as u16).min(MAX_REPAIRERS))
+	.load(Load::Full)]
+}
+
+/// Repair the most damaged structure in the room, relative to the hits it should have
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum RepairState {
+	Entry,
+	Repairing(CreepTarget),
+	Done,
+}
+
+impl Default for RepairState {
+	fn default() -> Self { Self::Entry }
+}
+
+impl Job for RepairState {
+	fn finished(&self) -> bool {
+		if let RepairState::Done = self {
+			true
+		}
+		else {
+			false
+		}
+	}
+
+	fn state_name(&self) -> &'static str {
+		match self {
+			RepairState::Entry => "Entry",
+			RepairState::Repairing(_) => "Repairing",
+			RepairState::Done => "Done",
+		}
+	}
+
+	fn target(&self) -> Option<&CreepTarget> {
+		match self {
+			RepairState::Repairing(target) => Some(target),
+			_ => None,
+		}
+	}
+
+	fn drive(&mut self, creep: &Creep, _ctx: &TickContext) -> Result<()> {
+		match self {
+			RepairState::Entry => {
+				let room = creep.room().ok_or(Error::Unknown)?;
+				let wall_hits = economy::policy(&room_name(&room)).wall_hits;
+
+				let worst = damaged(&room).into_iter().min_by(|a, b| {
+					let a = hits(a) as f64 / wanted_hits(a, wall_hits) as f64;
+					let b = hits(b) as f64 / wanted_hits(b, wall_hits) as f64;
+					a.partial_cmp(&b).unwrap()
+				});
+				match worst {
+					Some(structure) => {
+						*self = RepairState::Repairing(CreepTarget::from_id(&structure.id()));
+						creep.say(&JsString::from("Repair"), false);
+					}
+					None => *self = RepairState::Done,
+				}
+				Ok(())
+			}
+			RepairState::Repairing(target) => {
+				if creep.store().get_used_capacity(Some(ResourceType::Energy)) == 0 {
+					*self = RepairState::Done;
+					return Ok(());
+				}
+				let structure = match target.to_value() {
+					Ok(t) => Structure::from(t),
+					Err(_) => {
+						// Destroyed - retarget
+						*self = RepairState::Entry;
+						return Ok(());
+					}
+				};
+				let room = creep.room().ok_or(Error::Unknown)?;
+				let wall_hits = economy::policy(&room_name(&room)).wall_hits;
+				if hits(&structure) >= wanted_hits(&structure, wall_hits) {
+					*self = RepairState::Entry;
+					return Ok(());
+				}
+
+				match repair(creep, &structure) {
+					ReturnCode::Ok => Ok(()),
+					ReturnCode::NotInRange => {
+						move_to_object(creep, &RoomObject::from(JsValue::from(structure)), 3)
+					}
+					x => Err(Error::UnhandledErrorCode(x)),
+				}
+			}
+			RepairState::Done => Ok(()),
+		}
+	}
+}
@@ -0,0 +1,180 @@
+use wasm_bindgen::prelude::*;
+
+use js_sys::JsString;
+
+use serde::{Deserialize, Serialize};
+
+use screeps::{
+	constants::{ResourceType, ReturnCode},
+	objects::{Creep, Room, RoomObject, Structure},
+};
+
+use num_traits::cast::FromPrimitive;
+
+use crate::{
+	colony::Colony,
+	context::TickContext,
+	error::{Error, Result},
+	jobs::{
+		harvest::{closest_source, harvest_fallback},
+		CreepJob, Job, JobKind,
+	},
+	market::Offer,
+	movement::move_to_object,
+	structures::CreepTarget,
+	util::{room_name, ticks_to_downgrade},
+};
+
+/// Ticks to downgrade below which creeps carrying energy are pulled in to upgrade
+const DOWNGRADE_SAFETY: u32 = 5000;
+
+/// A single offer at the controller, taken by every idle upgrader
+pub fn offers(colony: &Colony, _ctx: &TickContext) -> Vec<Offer> {
+	match colony.room.controller() {
+		Some(controller) => vec![Offer::new(
+			&colony.name,
+			CreepJob::Upgrade(Default::default()),
+			JobKind::Upgrade.priority(),
+		)
+		.at(&controller)],
+		None => Vec::new(),
+	}
+}
+
+/// Whether a room's controller is close enough to losing a level that upgrading is urgent
+pub fn downgrading(room: &Room) -> bool {
+	room.controller()
+		.and_then(|controller| ticks_to_downgrade(&controller))
+		.map(|ticks| ticks < DOWNGRADE_SAFETY)
+		.unwrap_or(false)
+}
+
+/// Upgrade the controller, refilling from the controller's container or link, the storage, or
+/// failing those a source
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum UpgradeState {
+	Entry,
+	Withdrawing(CreepTarget),
+	Harvesting(CreepTarget),
+	Upgrading,
+	Done,
+}
+
+impl Default for UpgradeState {
+	fn default() -> Self { Self::Entry }
+}
+
+impl Job for UpgradeState {
+	fn finished(&self) -> bool {
+		if let UpgradeState::Done = self {
+			true
+		}
+		else {
+			false
+		}
+	}
+
+	fn state_name(&self) -> &'static str {
+		match self {
+			UpgradeState::Entry => "Entry",
+			UpgradeState::Withdrawing(_) => "Withdrawing",
+			UpgradeState::Harvesting(_) => "Harvesting",
+			UpgradeState::Upgrading => "Upgrading",
+			UpgradeState::Done => "Done",
+		}
+	}
+
+	fn target(&self) -> Option<&CreepTarget> {
+		match self {
+			UpgradeState::Withdrawing(target) | UpgradeState::Harvesting(target) => Some(target),
+			_ => None,
+		}
+	}
+
+	fn preempted(&mut self) {
+		if let UpgradeState::Withdrawing(_) | UpgradeState::Harvesting(_) = self {
+			*self = UpgradeState::Entry;
+		}
+	}
+
+	fn drive(&mut self, creep: &Creep, ctx: &TickContext) -> Result<()> {
+		match self {
+			UpgradeState::Entry => {
+				if creep.store().get_used_capacity(Some(ResourceType::Energy)) > 0 {
+					*self = UpgradeState::Upgrading;
+					creep.say(&JsString::from("Upgrade"), false);
+					return Ok(());
+				}
+
+				let room = creep.room().ok_or(Error::Unknown)?;
+
+				let feed = ctx.controller_feed(&room_name(&room)).cloned().or_else(|| {
+					room.storage()
+						.filter(|storage| {
+							storage
+								.store()
+								.get_used_capacity(Some(ResourceType::Energy))
+								> 0
+						})
+						.map(JsValue::from)
+						.map(Structure::from)
+				});
+				if let Some(feed) = feed {
+					*self = UpgradeState::Withdrawing(CreepTarget::from_id(&feed.id()));
+					return Ok(());
+				}
+
+				*self = UpgradeState::Harvesting(closest_source(creep)?);
+				Ok(())
+			}
+			UpgradeState::Withdrawing(target) => {
+				let target = match target.to_value() {
+					Ok(t) => t,
+					Err(_) => {
+						// Lost feed - retarget
+						*self = UpgradeState::Entry;
+						return Ok(());
+					}
+				};
+
+				match ReturnCode::from_i8(creep.withdraw(
+					&RoomObject::from(JsValue::from(&target)),
+					ResourceType::Energy,
+					None,
+				))
+				.unwrap()
+				{
+					ReturnCode::Ok | ReturnCode::Full => {
+						*self = UpgradeState::Upgrading;
+						Ok(())
+					}
+					ReturnCode::NotInRange => move_to_object(creep, &RoomObject::from(target), 1),
+					// Wait at the feed for it to be refilled
+					ReturnCode::NotEnough => Ok(()),
+					x => Err(Error::UnhandledErrorCode(x)),
+				}
+			}
+			UpgradeState::Harvesting(target) => {
+				if harvest_fallback(creep, target)? {
+					*self = UpgradeState::Entry;
+				}
+				Ok(())
+			}
+			UpgradeState::Upgrading => {
+				if creep.store().get_used_capacity(Some(ResourceType::Energy)) == 0 {
+					*self = UpgradeState::Done;
+					return Ok(());
+				}
+
+				let room = creep.room().ok_or(Error::Unknown)?;
+				let controller = room.controller().ok_or(Error::NoneFound)?;
+				match ReturnCode::from_i8(creep.upgrade_controller(&controller)).unwrap() {
+					ReturnCode::Ok => Ok(()),
+					ReturnCode::NotInRange => move_to_object(creep, &controller, 3),
+					x => Err(Error::UnhandledErrorCode(x)),
+				}
+			}
+			UpgradeState::Done => Ok(()),
+		}
+	}
+}
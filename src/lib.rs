@@ -1,99 +1,138 @@
 pub mod builds;
+pub mod codec;
+pub mod colony;
+pub mod commands;
+pub mod config;
+pub mod context;
+pub mod coords;
+pub mod cost_matrix;
+pub mod economy;
 pub mod error;
+pub mod intel;
+pub mod interrupts;
 pub mod jobs;
+pub mod lifecycle;
+pub mod links;
+pub mod logging;
+pub mod market;
+pub mod movement;
+pub mod pathfinding;
+pub mod pixels;
+pub mod profiler;
+pub mod scheduler;
+pub mod segments;
+pub mod stats;
 pub mod strategy;
 pub mod structures;
+pub mod travel;
 pub mod util;
+pub mod visuals;
 
 use wasm_bindgen::prelude::*;
 
 use js_sys::Object;
 
-use screeps::{
-	objects::{ConstructionSite, Creep, Flag, Room, Structure, StructureSpawn},
-	Game,
-};
+use screeps::{objects::Creep, Game};
 
 use std::collections::HashMap;
 
 use crate::{
+	colony::Colony,
+	context::TickContext,
 	error::Result,
-	strategy::{drive_creeps, execute_strategy},
-	util::{copy_state_in, copy_state_out, log},
+	log_error,
+	profiler::profile,
+	scheduler::Scheduler,
+	segments::SegmentKind,
+	strategy::{drive_creeps, execute_spawning, execute_strategy},
+	util::{copy_state_in, copy_state_out},
 };
 
 #[wasm_bindgen(js_name = setup)]
 pub fn setup_entry() {
 	match setup() {
 		Ok(_) => (),
-		Err(e) => log(format!("Setup error: {:?}", e)),
+		Err(e) => log_error!("setup error: {:?}", e),
 	}
 }
 
-fn setup() -> Result<()> { Ok(()) }
+fn setup() -> Result<()> {
+	config::load()?;
+	segments::request(SegmentKind::Intel);
+
+	Ok(())
+}
 
 #[wasm_bindgen(js_name = loop)]
 pub fn game_loop_entry() {
 	match game_loop() {
 		Ok(_) => (),
-		Err(e) => log(format!("Game loop error: {:?}", e)),
+		Err(e) => log_error!("game loop error: {:?}", e),
 	}
+	segments::flush();
 }
 
 fn game_loop() -> Result<()> {
-	let construction_sites = Object::values(&Game::construction_sites())
-		.iter()
-		.map(ConstructionSite::from)
-		.collect::<Vec<_>>();
+	let ctx = TickContext::gather();
 
 	let creeps = Object::values(&Game::creeps())
 		.iter()
 		.map(Creep::from)
 		.collect::<Vec<_>>();
 
-	let flags = Object::values(&Game::flags())
-		.iter()
-		.map(Flag::from)
-		.collect::<Vec<_>>();
+	let mut scheduler = Scheduler::start();
 
-	let rooms = Object::values(&Game::rooms())
-		.iter()
-		.map(Room::from)
-		.collect::<Vec<_>>();
+	// Cheap once done, and must run on the tick the requested segments are active
+	intel::restore()?;
 
-	let spawns = Object::values(&Game::spawns())
-		.iter()
-		.map(StructureSpawn::from)
-		.collect::<Vec<_>>();
+	scheduler.run(&scheduler::INTEL, || {
+		intel::gather(&ctx.rooms);
+		Ok(())
+	})?;
 
-	let structures = Object::values(&Game::structures())
-		.iter()
-		.map(Structure::from)
-		.collect::<Vec<_>>();
+	scheduler.run(&scheduler::MAINTENANCE, || {
+		travel::prune_routes();
+		cost_matrix::prune(&ctx.rooms, 1500);
+		movement::prune_paths();
+		profiler::prune();
+		logging::prune();
+		intel::save()?;
+		config::load()
+	})?;
 
 	// Pair each creep with its memory structure
-	let mut creep_pairs = creeps
-		.into_iter()
-		.map(copy_state_in)
-		.collect::<Result<Vec<_>>>()?;
-
-	execute_strategy(
-		&construction_sites,
-		&mut creep_pairs,
-		&flags,
-		&rooms,
-		&spawns,
-		&structures,
-	)?;
-
-	drive_creeps(
-		&construction_sites,
-		&mut creep_pairs,
-		&flags,
-		&rooms,
-		&spawns,
-		&structures,
-	)?;
+	let mut creep_pairs = creeps.into_iter().map(copy_state_in).collect::<Vec<_>>();
+
+	// Before any planner or job looks at what is left to collect
+	jobs::reserve_energy(&ctx, &creep_pairs);
+
+	let colonies = Colony::gather(&creep_pairs, &ctx.rooms, &ctx.spawns);
+
+	scheduler.run(&scheduler::STRATEGY, || {
+		profile("execute_strategy", || {
+			execute_strategy(&ctx, &mut creep_pairs, &colonies)
+		})
+	})?;
+
+	scheduler.run(&scheduler::SPAWNING, || {
+		profile("execute_spawning", || {
+			execute_spawning(&ctx, &creep_pairs, &colonies)
+		})
+	})?;
+
+	scheduler.run(&scheduler::CREEPS, || {
+		profile("drive_creeps", || drive_creeps(&ctx, &mut creep_pairs))
+	})?;
+
+	scheduler.run(&scheduler::MOVEMENT, || movement::resolve(&creep_pairs))?;
+
+	scheduler.run(&scheduler::LINKS, || links::run(&ctx))?;
+
+	scheduler.run(&scheduler::STATS, || {
+		stats::collect(&creep_pairs, &ctx.rooms, &ctx.spawns)
+	})?;
+
+	scheduler.run(&scheduler::VISUALS, || visuals::draw(&creep_pairs))?;
 
 	// Apply state changes
 	creep_pairs
@@ -101,5 +140,8 @@ fn game_loop() -> Result<()> {
 		.map(|(creep, state)| copy_state_out(&creep, state))
 		.collect::<Result<()>>()?;
 
+	let remaining = scheduler.remaining();
+	scheduler.run(&scheduler::PIXELS, || pixels::generate(remaining))?;
+
 	Ok(())
 }
@@ -0,0 +1,179 @@
+use serde::{Deserialize, Serialize};
+
+use screeps::{constants::ResourceType, objects::Creep, Game};
+
+use std::collections::HashMap;
+
+use crate::{
+	builds::CreepBuild,
+	colony::Colony,
+	config,
+	context::TickContext,
+	jobs::{CreepJob, JobKind, RenewState},
+	log_debug,
+	logging::Context,
+	strategy::recipe,
+	structures::CreepState,
+	util::{room_name, ticks_to_live, world_pos},
+};
+
+/// Ticks the spawn takes per body part
+const SPAWN_TICKS_PER_PART: u32 = 3;
+/// Ticks a creep works before trying again after a spawn turned it away from renewing
+const RENEW_BACKOFF: u32 = 50;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LifecycleConfig {
+	/// Renew creeps whose build is still in the colony's spawner recipe
+	pub renew: bool,
+	/// Ticks to live below which an idle creep goes to be renewed
+	pub renew_below: u32,
+	/// Recycle creeps whose build is no longer spawned once their replacements are out
+	pub recycle: bool,
+}
+
+impl Default for LifecycleConfig {
+	fn default() -> Self {
+		LifecycleConfig {
+			renew: true,
+			renew_below: 300,
+			recycle: true,
+		}
+	}
+}
+
+/// Ticks before a creep dies at which its replacement must start spawning to arrive in time
+fn lead_time(creep: &Creep, state: &CreepState, colony: &Colony) -> u32 {
+	let spawn_time = state.build.parts().count() * SPAWN_TICKS_PER_PART;
+	let travel = creep
+		.pos()
+		.as_ref()
+		.and_then(world_pos)
+		.map(|pos| {
+			colony
+				.spawns
+				.iter()
+				.filter_map(|spawner| world_pos(&spawner.pos()?))
+				.map(|spawner| spawner.range(&pos))
+				.min()
+				.unwrap_or(0)
+		})
+		.unwrap_or(0);
+	spawn_time + travel
+}
+
+/// Whether a creep dies before a replacement spawned now would take over its work. Creeps being
+/// renewed are expected to live on.
+fn expiring(creep: &Creep, state: &CreepState, colony: &Colony) -> bool {
+	if state.job.kind() == JobKind::Renew {
+		return false;
+	}
+	ticks_to_live(creep)
+		.map(|ticks| ticks < lead_time(creep, state, colony))
+		.unwrap_or(false)
+}
+
+/// Creeps per build in a colony which will outlive the spawning of a replacement, so the spawner
+/// queues replacements for the others ahead of time
+pub fn lasting_creeps(
+	creep_pairs: &[(Creep, CreepState)],
+	colony: &Colony,
+) -> HashMap<CreepBuild, u16> {
+	let mut creeps_by_build = HashMap::new();
+	creep_pairs
+		.iter()
+		.filter(|(_, state)| state.home == colony.name)
+		.filter(|(creep, state)| !expiring(creep, state, colony))
+		.for_each(|(_, state)| *creeps_by_build.entry(state.build.clone()).or_insert(0) += 1);
+	creeps_by_build
+}
+
+/// Lifecycle job for an idle creep, if it is due one
+fn due(
+	ctx: &TickContext,
+	creep: &Creep,
+	state: &CreepState,
+	colony: &Colony,
+	config: &LifecycleConfig,
+	recipe_full: bool,
+) -> Option<CreepJob> {
+	// Cargo is delivered first, the market hands loaded creeps a delivery job
+	if creep.store().get_used_capacity(Some(ResourceType::Energy)) > 0 {
+		return None;
+	}
+	// Both jobs need a spawn in the room the creep is in
+	if creep.room().as_ref().map(room_name).as_ref() != Some(&colony.name) {
+		return None;
+	}
+
+	// Dedicated builds are spawned outside the recipe, and are obsolete once there is no work left
+	// for them
+	let obsolete = match colony.dedicated_target(ctx, &state.build) {
+		Some(target) => target == 0,
+		None => !recipe(colony).contains_key(&state.build),
+	};
+	if obsolete {
+		if config.recycle && recipe_full {
+			return Some(CreepJob::Recycle(Default::default()));
+		}
+		return None;
+	}
+
+	let room = &colony.room;
+	let dying = ticks_to_live(creep)
+		.map(|ticks| ticks < config.renew_below)
+		.unwrap_or(false);
+	// Leave the energy to the spawner while the room is low
+	let affordable = room.energy_available() * 2 >= room.energy_capacity_available();
+	// A spawn renews nothing while it is spawning, so the creep would only wait there
+	let idle_spawn = colony
+		.spawns
+		.iter()
+		.any(|spawner| spawner.spawning().is_none());
+	let refused = state
+		.renew_refused
+		.map(|tick| Game::time() < tick + RENEW_BACKOFF)
+		.unwrap_or(false);
+	if config.renew && dying && affordable && idle_spawn && !refused {
+		return Some(CreepJob::Renew(Default::default()));
+	}
+
+	None
+}
+
+/// Remember a spawn turning a creep away from renewing, before its finished job is released
+pub fn note_refusal(state: &mut CreepState) {
+	if let CreepJob::Renew(RenewState::Refused(tick)) = state.job {
+		state.renew_refused = Some(tick);
+	}
+}
+
+/// Send idle creeps to be renewed or recycled
+pub fn apply(ctx: &TickContext, creep_pairs: &mut [(Creep, CreepState)], colonies: &[Colony]) {
+	let config = config::with(|config| config.lifecycle.clone());
+
+	for colony in colonies {
+		// Obsolete creeps keep working until the current recipe has its full population
+		let lasting = lasting_creeps(creep_pairs, colony);
+		let recipe = recipe(colony);
+		let recipe_full = lasting
+			.iter()
+			.filter(|(build, _)| recipe.contains_key(build))
+			.map(|(_, count)| count)
+			.sum::<u16>()
+			>= colony.population_target();
+
+		for (creep, state) in creep_pairs.iter_mut() {
+			if state.home != colony.name || state.job.kind() != JobKind::None {
+				continue;
+			}
+			if let Some(job) = due(ctx, creep, state, colony, &config, recipe_full) {
+				state.job = job;
+				let context =
+					Context::creep(creep.name().as_string().unwrap(), Some(colony.name.clone()));
+				log_debug!(ctx: context, "{} {}", state.job.name(), state.build);
+			}
+		}
+	}
+}
@@ -0,0 +1,145 @@
+use wasm_bindgen::prelude::*;
+
+use screeps::{
+	constants::{ResourceType, ReturnCode, StructureType},
+	objects::StructureLink,
+};
+
+use crate::{
+	context::TickContext,
+	coords::WorldPos,
+	error::Result,
+	log_warn,
+	logging::Context,
+	util::{cooldown, room_name, transfer_energy, world_pos},
+};
+
+/// Range from a source or the storage within which a link belongs to it
+const NEAR_RANGE: u32 = 2;
+/// Energy a source link collects before sending, to keep the 3% loss per transfer down
+const MIN_SEND: u32 = 400;
+
+/// Links in a room by what they are next to
+#[derive(Default)]
+pub struct RoomLinks {
+	/// Filled by harvesters, and emptied into the other links
+	pub sources: Vec<StructureLink>,
+	/// Feeds the upgraders
+	pub controller: Option<StructureLink>,
+	/// Next to the storage, taking whatever the controller does not need
+	pub hub: Option<StructureLink>,
+}
+
+impl RoomLinks {
+	/// Whether energy sent from the source links has anywhere to go
+	pub fn receives(&self) -> bool { self.controller.is_some() || self.hub.is_some() }
+}
+
+fn pos_of(link: &StructureLink) -> Option<WorldPos> { link.pos().as_ref().and_then(world_pos) }
+
+fn energy(link: &StructureLink) -> u32 {
+	link.store().get_used_capacity(Some(ResourceType::Energy))
+}
+
+fn free(link: &StructureLink) -> u32 { link.store().get_free_capacity(Some(ResourceType::Energy)) }
+
+/// Sort a room's links by their position. The controller's feed takes precedence over the other
+/// roles, so a link between the controller and a source feeds the upgraders.
+pub fn classify(ctx: &TickContext, room: &str) -> RoomLinks {
+	let controller = ctx
+		.controller_feed(room)
+		.filter(|feed| feed.structure_type() == StructureType::Link)
+		.map(|feed| StructureLink::from(JsValue::from(feed)));
+	let controller_id = controller.as_ref().and_then(|link| link.id().as_string());
+
+	let sources = ctx
+		.sources_in(room)
+		.iter()
+		.filter_map(|source| source.pos().as_ref().and_then(world_pos))
+		.collect::<Vec<_>>();
+	let storage = ctx
+		.room(room)
+		.and_then(|room| room.storage())
+		.and_then(|storage| storage.pos().as_ref().and_then(world_pos));
+
+	let mut links = RoomLinks {
+		controller,
+		..Default::default()
+	};
+	ctx.structures_in(room, StructureType::Link)
+		.iter()
+		.map(JsValue::from)
+		.map(StructureLink::from)
+		.filter(|link| link.id().as_string() != controller_id)
+		.for_each(|link| {
+			let pos = match pos_of(&link) {
+				Some(pos) => pos,
+				None => return,
+			};
+			if sources
+				.iter()
+				.any(|source| source.range(&pos) <= NEAR_RANGE)
+			{
+				links.sources.push(link);
+			}
+			else if storage.map(|storage| storage.range(&pos) <= NEAR_RANGE) == Some(true) {
+				links.hub = Some(link);
+			}
+		});
+	links
+}
+
+/// Source link a harvester standing at `pos` can fill without moving
+pub fn source_link_at(ctx: &TickContext, room: &str, pos: WorldPos) -> Option<StructureLink> {
+	ctx.links(room)?
+		.sources
+		.iter()
+		.find(|link| pos_of(link).map(|link| link.range(&pos) <= 1) == Some(true))
+		.cloned()
+}
+
+/// Send energy from full enough source links to the controller link while it has room, and to
+/// the hub link otherwise
+fn transfer(room: &str, links: &RoomLinks) {
+	let mut controller_free = links.controller.as_ref().map(free).unwrap_or(0);
+	let mut hub_free = links.hub.as_ref().map(free).unwrap_or(0);
+
+	for link in &links.sources {
+		let energy = energy(link);
+		if cooldown(link) > 0 || energy < MIN_SEND {
+			continue;
+		}
+
+		let (target, room_left) = if controller_free >= MIN_SEND {
+			(links.controller.as_ref(), &mut controller_free)
+		}
+		else if hub_free > 0 {
+			(links.hub.as_ref(), &mut hub_free)
+		}
+		else {
+			continue;
+		};
+		let target = match target {
+			Some(target) => target,
+			None => continue,
+		};
+
+		let amount = energy.min(*room_left);
+		match transfer_energy(link, target, Some(amount)) {
+			ReturnCode::Ok => *room_left -= amount,
+			x => {
+				log_warn!(ctx: Context::room(room), "link transfer failed: {:?}", x);
+			}
+		}
+	}
+}
+
+/// Move energy through the links of every room we have them in
+pub fn run(ctx: &TickContext) -> Result<()> {
+	ctx.rooms.iter().map(room_name).for_each(|room| {
+		if let Some(links) = ctx.links(&room).filter(|links| links.receives()) {
+			transfer(&room, links);
+		}
+	});
+	Ok(())
+}
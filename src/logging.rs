@@ -0,0 +1,247 @@
+use wasm_bindgen::prelude::*;
+
+use serde::{Deserialize, Serialize};
+
+use once_cell::sync::Lazy;
+
+use screeps::Game;
+
+use std::{
+	collections::{hash_map::DefaultHasher, HashMap},
+	fmt::{Display, Formatter},
+	hash::{Hash, Hasher},
+	sync::Mutex,
+};
+
+use crate::{config, util::log};
+
+/// Minutes `Game.notify` groups repeated error emails over
+const NOTIFY_INTERVAL: u32 = 60;
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(js_namespace = Game, js_name = notify)]
+	fn game_notify(message: &str, group_interval: u32);
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub enum Level {
+	Trace,
+	Debug,
+	Info,
+	Warn,
+	Error,
+}
+
+impl Level {
+	fn colour(&self) -> &'static str {
+		match self {
+			Level::Trace => "#666666",
+			Level::Debug => "#999999",
+			Level::Info => "#cccccc",
+			Level::Warn => "#f0c040",
+			Level::Error => "#ff5050",
+		}
+	}
+}
+
+impl Display for Level {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result { write!(f, "{:?}", self) }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+	pub level: Level,
+	/// Level overrides by module name, such as `movement` or `jobs::harvest`
+	pub modules: HashMap<String, Level>,
+	/// When not empty only messages about these rooms are shown
+	pub rooms: Vec<String>,
+	/// When not empty only messages about these creeps are shown
+	pub creeps: Vec<String>,
+	/// Identical messages are shown at most once per this many ticks
+	pub throttle_ticks: u32,
+	pub notify_errors: bool,
+}
+
+impl Default for LogConfig {
+	fn default() -> Self {
+		LogConfig {
+			level: Level::Info,
+			modules: HashMap::new(),
+			rooms: Vec::new(),
+			creeps: Vec::new(),
+			throttle_ticks: 50,
+			notify_errors: true,
+		}
+	}
+}
+
+impl LogConfig {
+	fn allows(&self, level: Level, module: &str, context: &Context) -> bool {
+		// The most specific module override wins
+		let threshold = self
+			.modules
+			.iter()
+			.filter(|(name, _)| {
+				module == name.as_str() || module.starts_with(&format!("{}::", name))
+			})
+			.max_by_key(|(name, _)| name.len())
+			.map(|(_, level)| *level)
+			.unwrap_or(self.level);
+
+		let room_allowed = match &context.room {
+			Some(room) if !self.rooms.is_empty() => self.rooms.contains(room),
+			_ => true,
+		};
+		let creep_allowed = match &context.creep {
+			Some(creep) if !self.creeps.is_empty() => self.creeps.contains(creep),
+			_ => true,
+		};
+
+		level >= threshold && room_allowed && creep_allowed
+	}
+}
+
+/// What a message is about, used for filtering
+#[derive(Clone, Debug, Default)]
+pub struct Context {
+	pub room: Option<String>,
+	pub creep: Option<String>,
+}
+
+impl Context {
+	pub fn room(room: impl Into<String>) -> Self {
+		Context {
+			room: Some(room.into()),
+			creep: None,
+		}
+	}
+
+	pub fn creep(creep: impl Into<String>, room: Option<String>) -> Self {
+		Context {
+			room,
+			creep: Some(creep.into()),
+		}
+	}
+}
+
+struct Seen {
+	last_shown: u32,
+	suppressed: u32,
+}
+
+static SEEN: Lazy<Mutex<HashMap<u64, Seen>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Write a message through the filters. Usually called through the `log_*!` macros.
+pub fn write(level: Level, module: &str, context: &Context, message: String) {
+	let module = module.trim_start_matches("screeps_oxide::");
+
+	let (allowed, throttle_ticks, notify) = config::with(|config| {
+		let logging = &config.logging;
+		(
+			logging.allows(level, module, context),
+			logging.throttle_ticks,
+			logging.notify_errors,
+		)
+	});
+	if !allowed {
+		return;
+	}
+
+	let mut hasher = DefaultHasher::new();
+	(level, module, &message).hash(&mut hasher);
+	let key = hasher.finish();
+
+	let time = Game::time();
+	let suppressed = {
+		let mut seen = SEEN.lock().unwrap();
+		let entry = seen.entry(key).or_insert(Seen {
+			last_shown: 0,
+			suppressed: 0,
+		});
+		if entry.last_shown != 0 && time.saturating_sub(entry.last_shown) < throttle_ticks {
+			entry.suppressed += 1;
+			return;
+		}
+		let suppressed = entry.suppressed;
+		entry.last_shown = time;
+		entry.suppressed = 0;
+		suppressed
+	};
+
+	let mut prefix = format!("[{}] {}", level, module);
+	if let Some(room) = &context.room {
+		prefix.push_str(&format!(" {}", room));
+	}
+	if let Some(creep) = &context.creep {
+		prefix.push_str(&format!(" {}", creep));
+	}
+	let repeats = if suppressed > 0 {
+		format!(" (repeated {} times)", suppressed)
+	}
+	else {
+		String::new()
+	};
+
+	log(format!(
+		"<span style=\"color: {}\">{}: {}{}</span>",
+		level.colour(),
+		prefix,
+		message,
+		repeats
+	));
+
+	if level == Level::Error && notify {
+		game_notify(&format!("{}: {}", prefix, message), NOTIFY_INTERVAL);
+	}
+}
+
+/// Forget messages which have not been repeated recently
+pub fn prune() {
+	let time = Game::time();
+	let throttle_ticks = config::with(|config| config.logging.throttle_ticks);
+	SEEN.lock()
+		.unwrap()
+		.retain(|_, seen| time.saturating_sub(seen.last_shown) < throttle_ticks);
+}
+
+#[macro_export]
+macro_rules! log_at {
+	($level:expr, ctx: $ctx:expr, $($arg:tt)+) => {
+		$crate::logging::write($level, module_path!(), &$ctx, format!($($arg)+))
+	};
+	($level:expr, $($arg:tt)+) => {
+		$crate::logging::write(
+			$level,
+			module_path!(),
+			&$crate::logging::Context::default(),
+			format!($($arg)+),
+		)
+	};
+}
+
+#[macro_export]
+macro_rules! log_error {
+	($($arg:tt)+) => { $crate::log_at!($crate::logging::Level::Error, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! log_warn {
+	($($arg:tt)+) => { $crate::log_at!($crate::logging::Level::Warn, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! log_info {
+	($($arg:tt)+) => { $crate::log_at!($crate::logging::Level::Info, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! log_debug {
+	($($arg:tt)+) => { $crate::log_at!($crate::logging::Level::Debug, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! log_trace {
+	($($arg:tt)+) => { $crate::log_at!($crate::logging::Level::Trace, $($arg)+) };
+}
@@ -0,0 +1,183 @@
+use screeps::{
+	constants::ResourceType,
+	objects::{Creep, RoomObject},
+};
+
+use crate::{
+	builds::CreepBuild,
+	colony::Colony,
+	context::TickContext,
+	coords::WorldPos,
+	jobs::{CreepJob, JobKind, PLANNERS},
+	log_debug,
+	logging::Context,
+	structures::{CreepParts, CreepState},
+	util::world_pos,
+};
+
+/// How full a creep's store must be to take a job
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Load {
+	/// At least half of the store is free
+	Empty,
+	/// More than half of the store holds energy
+	Full,
+	Any,
+}
+
+impl Load {
+	fn allows(&self, free: u32, used: u32) -> bool {
+		match self {
+			Load::Empty => free >= used,
+			Load::Full => used > free,
+			Load::Any => true,
+		}
+	}
+}
+
+/// A job a colony wants done, published by a job's planner
+pub struct Offer {
+	pub room: String,
+	pub job: CreepJob,
+	/// Higher priority offers are filled first
+	pub priority: u8,
+	pub required: CreepParts,
+	/// Where the work happens, used to prefer nearby creeps
+	pub pos: Option<WorldPos>,
+	/// Creeps wanted, including those already working it
+	pub slots: u16,
+	pub load: Load,
+}
+
+impl Offer {
+	pub fn new(room: &str, job: CreepJob, priority: u8) -> Self {
+		Offer {
+			room: room.to_string(),
+			required: job.min_required(),
+			job,
+			priority,
+			pos: None,
+			slots: u16::MAX,
+			load: Load::Any,
+		}
+	}
+
+	pub fn at(mut self, target: &RoomObject) -> Self {
+		self.pos = target.pos().as_ref().and_then(world_pos);
+		self
+	}
+
+	pub fn slots(mut self, slots: u16) -> Self {
+		self.slots = slots;
+		self
+	}
+
+	pub fn load(mut self, load: Load) -> Self {
+		self.load = load;
+		self
+	}
+
+	/// Whether a creep is already working this offer
+	fn taken_by(&self, state: &CreepState) -> bool {
+		state.home == self.room
+			&& state.job.kind() == self.job.kind()
+			&& state.job.target() == self.job.target()
+	}
+}
+
+/// An idle creep, with what the market needs to know about it read once
+struct Candidate {
+	index: usize,
+	home: String,
+	role: Option<JobKind>,
+	parts: CreepParts,
+	free: u32,
+	used: u32,
+	pos: Option<WorldPos>,
+}
+
+impl Candidate {
+	fn new(index: usize, creep: &Creep, state: &CreepState) -> Self {
+		let store = creep.store();
+		Candidate {
+			index,
+			home: state.home.clone(),
+			role: state.build.role(),
+			parts: state.build.parts(),
+			free: store.get_free_capacity(Some(ResourceType::Energy)),
+			used: store.get_used_capacity(Some(ResourceType::Energy)),
+			pos: creep.pos().as_ref().and_then(world_pos),
+		}
+	}
+
+	fn eligible(&self, offer: &Offer) -> bool {
+		let kind = offer.job.kind();
+		let suited = match self.role {
+			Some(role) => role == kind,
+			None => !CreepBuild::dedicated(kind),
+		};
+		self.home == offer.room
+			&& suited && offer.load.allows(self.free, self.used)
+			&& self.parts.fulfils_requirements(&offer.required)
+	}
+
+	fn distance(&self, offer: &Offer) -> u32 {
+		match (&self.pos, &offer.pos) {
+			(Some(pos), Some(target)) => pos.range(target),
+			_ => 0,
+		}
+	}
+}
+
+/// Collect the offers of every colony and hand them out to idle creeps, highest priority first
+/// and to the closest eligible creep
+pub fn assign(ctx: &TickContext, creep_pairs: &mut [(Creep, CreepState)], colonies: &[Colony]) {
+	let mut offers = colonies
+		.iter()
+		.flat_map(|colony| {
+			PLANNERS
+				.iter()
+				.flat_map(move |planner| planner(colony, ctx))
+		})
+		.collect::<Vec<_>>();
+	offers.sort_by(|a, b| b.priority.cmp(&a.priority));
+
+	offers.iter_mut().for_each(|offer| {
+		let taken = creep_pairs
+			.iter()
+			.filter(|(_, state)| offer.taken_by(state))
+			.count();
+		offer.slots = offer.slots.saturating_sub(taken as u16);
+	});
+
+	let mut idle = creep_pairs
+		.iter()
+		.enumerate()
+		.filter(|(_, (_, state))| state.job.kind() == JobKind::None)
+		.map(|(index, (creep, state))| Candidate::new(index, creep, state))
+		.collect::<Vec<_>>();
+
+	for offer in &offers {
+		for _ in 0..offer.slots {
+			let best = idle
+				.iter()
+				.enumerate()
+				.filter(|(_, candidate)| candidate.eligible(offer))
+				.min_by_key(|(_, candidate)| candidate.distance(offer))
+				.map(|(position, _)| position);
+			let candidate = match best {
+				Some(position) => idle.swap_remove(position),
+				None => break,
+			};
+
+			let (creep, state) = &mut creep_pairs[candidate.index];
+			state.job = offer.job.clone();
+			let context =
+				Context::creep(creep.name().as_string().unwrap(), Some(offer.room.clone()));
+			log_debug!(ctx: context, "assigned {} at priority {}", state.job.label(), offer.priority);
+		}
+		if idle.is_empty() {
+			break;
+		}
+	}
+}
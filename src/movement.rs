@@ -0,0 +1,252 @@
+use wasm_bindgen::prelude::*;
+
+use once_cell::sync::Lazy;
+
+use screeps::{
+	constants::ReturnCode,
+	objects::{Creep, RoomObject, RoomPosition},
+	Game,
+};
+
+use num_traits::cast::FromPrimitive;
+
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::{
+	coords::WorldPos,
+	error::{Error, Result},
+	jobs::JobKind,
+	log_warn,
+	logging::Context,
+	structures::CreepState,
+	travel::find_path,
+	util::world_pos,
+};
+
+/// Ticks a creep may fail to move before its path is recomputed around other creeps
+const STUCK_TICKS: u8 = 2;
+/// Ticks an unused creep path is kept around before being dropped
+const PATH_TTL: u32 = 50;
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(method, js_name = move)]
+	fn move_direction(this: &Creep, direction: u8) -> i8;
+}
+
+#[derive(Clone, Copy, Debug)]
+struct MoveIntent {
+	target: WorldPos,
+	range: u32,
+}
+
+/// Path stored as an origin followed by a string of direction digits, the same shape as the
+/// serialised paths of the game API
+#[derive(Clone, Debug)]
+struct CachedPath {
+	target: WorldPos,
+	range: u32,
+	origin: WorldPos,
+	directions: String,
+	last_pos: WorldPos,
+	stuck: u8,
+	last_used: u32,
+}
+
+impl CachedPath {
+	fn new(origin: WorldPos, target: WorldPos, range: u32, steps: &[WorldPos]) -> Self {
+		let mut directions = String::with_capacity(steps.len());
+		let mut previous = origin;
+		for step in steps {
+			// Exits move the creep onto the edge of the next room, which the search result may
+			// skip over
+			while previous.range(step) > 0 {
+				let direction = previous.direction_to(step).unwrap();
+				directions.push((b'0' + direction) as char);
+				previous = previous.offset(direction);
+			}
+		}
+
+		CachedPath {
+			target,
+			range,
+			origin,
+			directions,
+			last_pos: origin,
+			stuck: 0,
+			last_used: Game::time(),
+		}
+	}
+
+	/// Direction to take from `pos`, trimming the steps already taken. `None` if `pos` is not on
+	/// the path.
+	fn next_direction(&mut self, pos: WorldPos) -> Option<u8> {
+		let mut step = self.origin;
+		for (index, direction) in self.directions.bytes().enumerate() {
+			if step == pos {
+				self.origin = step;
+				self.directions.drain(..index);
+				return Some(direction - b'0');
+			}
+			step = step.offset(direction - b'0');
+		}
+		None
+	}
+}
+
+static INTENTS: Lazy<Mutex<HashMap<String, MoveIntent>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+static PATHS: Lazy<Mutex<HashMap<String, CachedPath>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Ask for a creep to be moved towards a position. Moves are performed together in `resolve`.
+pub fn move_to(creep: &Creep, target: &RoomPosition, range: u32) -> Result<()> {
+	let name = creep.name().as_string().unwrap();
+	let target = world_pos(target).ok_or(Error::NoneFound)?;
+	INTENTS
+		.lock()
+		.unwrap()
+		.insert(name, MoveIntent { target, range });
+	Ok(())
+}
+
+pub fn move_to_object(creep: &Creep, target: &RoomObject, range: u32) -> Result<()> {
+	move_to(creep, &target.pos().ok_or(Error::Unknown)?, range)
+}
+
+/// Direction the creep wants to go this tick, following or refreshing its cached path
+fn plan_step(
+	name: &str,
+	pos: WorldPos,
+	tired: bool,
+	intent: &MoveIntent,
+	occupied: &HashMap<WorldPos, usize>,
+) -> Result<Option<u8>> {
+	let mut paths = PATHS.lock().unwrap();
+
+	let cached = paths
+		.get_mut(name)
+		.filter(|path| path.target == intent.target && path.range == intent.range);
+
+	let stuck = match cached {
+		Some(path) => {
+			if path.last_pos == pos && !tired {
+				path.stuck += 1;
+			}
+			else {
+				path.stuck = 0;
+			}
+			path.last_pos = pos;
+			path.last_used = Game::time();
+
+			if path.stuck < STUCK_TICKS {
+				if let Some(direction) = path.next_direction(pos) {
+					return Ok(Some(direction));
+				}
+			}
+			path.stuck >= STUCK_TICKS
+		}
+		None => false,
+	};
+
+	// Route around every creep if the current path has stopped making progress
+	let blocked = if stuck {
+		occupied.keys().copied().filter(|p| *p != pos).collect()
+	}
+	else {
+		Vec::new()
+	};
+
+	let steps = find_path(pos, intent.target, intent.range, &blocked)?;
+	let mut path = CachedPath::new(pos, intent.target, intent.range, &steps);
+	let direction = path.next_direction(pos);
+	paths.insert(name.to_string(), path);
+
+	Ok(direction)
+}
+
+/// Resolve every movement intent submitted this tick. Creeps moving into each other swap places
+/// and idle creeps standing in the way are pushed back along the mover's path.
+pub fn resolve(creep_pairs: &[(Creep, CreepState)]) -> Result<()> {
+	let intents = INTENTS.lock().unwrap().drain().collect::<HashMap<_, _>>();
+
+	let positions = creep_pairs
+		.iter()
+		.map(|(creep, _)| creep.pos().as_ref().and_then(world_pos))
+		.collect::<Vec<_>>();
+
+	let occupied = positions
+		.iter()
+		.enumerate()
+		.filter_map(|(index, pos)| pos.map(|pos| (pos, index)))
+		.collect::<HashMap<_, _>>();
+
+	// Work out where everyone wants to go
+	let mut moves = vec![None; creep_pairs.len()];
+	for (index, (creep, _)) in creep_pairs.iter().enumerate() {
+		let name = creep.name().as_string().unwrap();
+		let (intent, pos) = match (intents.get(&name), positions[index]) {
+			(Some(intent), Some(pos)) => (intent, pos),
+			_ => continue,
+		};
+		if pos.range(&intent.target) <= intent.range {
+			continue;
+		}
+		let tired = creep.fatigue() > 0;
+		match plan_step(&name, pos, tired, intent, &occupied) {
+			Ok(Some(direction)) if !tired => moves[index] = Some(direction),
+			Ok(_) => (),
+			Err(e) => log_warn!(
+				ctx: Context::creep(name.clone(), Some(pos.room().to_string())),
+				"could not path to {:?}: {}",
+				intent.target,
+				e
+			),
+		}
+	}
+
+	// Clear the way for movers blocked by our own creeps
+	for index in 0..creep_pairs.len() {
+		let (direction, pos) = match (moves[index], positions[index]) {
+			(Some(direction), Some(pos)) => (direction, pos),
+			_ => continue,
+		};
+		let destination = pos.offset(direction);
+		let other = match occupied.get(&destination) {
+			Some(other) => *other,
+			None => continue,
+		};
+		if moves[other].is_some() {
+			// The other creep is moving anyway, and if it is coming here the game swaps us
+			continue;
+		}
+		let (other_creep, other_state) = &creep_pairs[other];
+		if other_state.job.kind() == JobKind::None && other_creep.fatigue() == 0 {
+			// Shove the idle creep into the space we are leaving
+			moves[other] = destination.direction_to(&pos);
+		}
+	}
+
+	for (index, (creep, _)) in creep_pairs.iter().enumerate() {
+		if let Some(direction) = moves[index] {
+			match ReturnCode::from_i8(creep.move_direction(direction)).unwrap() {
+				ReturnCode::Ok | ReturnCode::Tired | ReturnCode::Busy => (),
+				x => log_warn!(
+					ctx: Context::creep(creep.name().as_string().unwrap(), None),
+					"could not move: {}",
+					Error::UnhandledErrorCode(x)
+				),
+			}
+		}
+	}
+
+	Ok(())
+}
+
+/// Drop paths of creeps which have not moved recently, usually because they died
+pub fn prune_paths() {
+	let time = Game::time();
+	PATHS
+		.lock()
+		.unwrap()
+		.retain(|_, path| time.saturating_sub(path.last_used) < PATH_TTL);
+}
@@ -0,0 +1,542 @@
+use std::{
+	cmp::Reverse,
+	collections::{BinaryHeap, HashMap, VecDeque},
+};
+
+use crate::{
+	coords::{RoomCoord, WorldPos},
+	cost_matrix::{RoomCosts, IMPASSABLE, ROOM_AREA},
+};
+
+const TERRAIN_MASK_WALL: u8 = 1;
+const TERRAIN_MASK_SWAMP: u8 = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Terrain {
+	Plain,
+	Swamp,
+	Wall,
+}
+
+/// Terrain of a single room, in the same layout as `Room.Terrain.getRawBuffer`
+#[derive(Clone, Debug)]
+pub struct RoomTerrain(pub Vec<u8>);
+
+impl RoomTerrain {
+	pub fn from_raw(raw: &[u8]) -> Self { RoomTerrain(raw.to_vec()) }
+
+	/// Build terrain from 50 rows of `.` (plain), `~` (swamp) and `#` (wall)
+	pub fn from_rows(rows: &[&str]) -> Self {
+		let mut raw = vec![0; ROOM_AREA];
+		rows.iter().take(50).enumerate().for_each(|(y, row)| {
+			row.bytes().take(50).enumerate().for_each(|(x, tile)| {
+				raw[y * 50 + x] = match tile {
+					b'#' => TERRAIN_MASK_WALL,
+					b'~' => TERRAIN_MASK_SWAMP,
+					_ => 0,
+				}
+			})
+		});
+		RoomTerrain(raw)
+	}
+
+	pub fn get(&self, x: u8, y: u8) -> Terrain {
+		let raw = self.0[y as usize * 50 + x as usize];
+		if raw & TERRAIN_MASK_WALL != 0 {
+			Terrain::Wall
+		}
+		else if raw & TERRAIN_MASK_SWAMP != 0 {
+			Terrain::Swamp
+		}
+		else {
+			Terrain::Plain
+		}
+	}
+}
+
+/// Options mirroring those of `PathFinder.search`
+#[derive(Clone, Copy, Debug)]
+pub struct SearchOptions {
+	pub plain_cost: u32,
+	pub swamp_cost: u32,
+	pub max_ops: u32,
+	pub max_cost: u32,
+	/// Multiplier on the heuristic. 1 keeps results optimal, 0 turns the search into Dijkstra
+	pub heuristic_weight: u32,
+}
+
+impl Default for SearchOptions {
+	fn default() -> Self {
+		SearchOptions {
+			plain_cost: 2,
+			swamp_cost: 10,
+			max_ops: 20000,
+			max_cost: u32::MAX,
+			heuristic_weight: 1,
+		}
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+	/// Positions to step through, excluding the origin
+	pub path: Vec<WorldPos>,
+	pub cost: u32,
+	pub ops: u32,
+	pub incomplete: bool,
+}
+
+/// Rooms available to a search. Rooms without terrain are treated as impassable.
+#[derive(Default)]
+pub struct SearchRooms {
+	terrain: HashMap<RoomCoord, RoomTerrain>,
+	costs: HashMap<RoomCoord, RoomCosts>,
+}
+
+impl SearchRooms {
+	pub fn new() -> Self { Default::default() }
+
+	pub fn with_terrain(mut self, room: RoomCoord, terrain: RoomTerrain) -> Self {
+		self.terrain.insert(room, terrain);
+		self
+	}
+
+	/// Matrix costs override terrain costs wherever they are non-zero, like a PathFinder cost
+	/// matrix
+	pub fn with_costs(mut self, room: RoomCoord, costs: RoomCosts) -> Self {
+		self.costs.insert(room, costs);
+		self
+	}
+
+	/// Cheapest step anywhere in the rooms. Matrix costs such as roads can be below the terrain
+	/// costs, and the heuristic must not overestimate them to keep results optimal.
+	fn min_step(&self, options: &SearchOptions) -> u32 {
+		self.costs
+			.values()
+			.flat_map(|costs| costs.0.iter())
+			.filter(|cost| **cost != 0 && **cost != IMPASSABLE)
+			.map(|cost| *cost as u32)
+			.fold(options.plain_cost.min(options.swamp_cost), u32::min)
+			.max(1)
+	}
+
+	/// Cost of stepping onto a tile, `None` if it cannot be entered
+	fn cost(&self, pos: WorldPos, options: &SearchOptions) -> Option<u32> {
+		let room = pos.room();
+		let (x, y) = pos.local();
+		let terrain = self.terrain.get(&room)?;
+
+		match self.costs.get(&room).map(|costs| costs.get(x, y)) {
+			Some(IMPASSABLE) => return None,
+			Some(0) | None => (),
+			Some(cost) => return Some(cost as u32),
+		}
+
+		match terrain.get(x, y) {
+			Terrain::Plain => Some(options.plain_cost),
+			Terrain::Swamp => Some(options.swamp_cost),
+			Terrain::Wall => None,
+		}
+	}
+}
+
+fn neighbours(pos: WorldPos) -> impl Iterator<Item = WorldPos> {
+	(1..=8)
+		.map(move |direction| pos.offset(direction))
+		.filter(move |next| {
+			// Room borders can only be crossed orthogonally
+			next.room() == pos.room() || next.x == pos.x || next.y == pos.y
+		})
+}
+
+fn distance_to_goal(pos: WorldPos, goals: &[(WorldPos, u32)]) -> u32 {
+	goals
+		.iter()
+		.map(|(goal, range)| pos.range(goal).saturating_sub(*range))
+		.min()
+		.unwrap_or(0)
+}
+
+/// A* search from `origin` to within range of any of the goals, with the same cost model as the
+/// in game PathFinder. If no goal can be reached the path to the closest explored tile is returned
+/// and marked incomplete.
+pub fn search(
+	origin: WorldPos,
+	goals: &[(WorldPos, u32)],
+	rooms: &SearchRooms,
+	options: &SearchOptions,
+) -> SearchResult {
+	let min_cost = rooms.min_step(options);
+	let heuristic = |pos| distance_to_goal(pos, goals) * min_cost * options.heuristic_weight;
+
+	let mut open = BinaryHeap::new();
+	let mut best = HashMap::<WorldPos, (u32, Option<WorldPos>)>::new();
+
+	best.insert(origin, (0, None));
+	open.push(Reverse((heuristic(origin), 0, origin.x, origin.y)));
+
+	let mut closest = (distance_to_goal(origin, goals), origin);
+	let mut ops = 0;
+	let mut found = None;
+
+	while let Some(Reverse((_, cost, x, y))) = open.pop() {
+		let pos = WorldPos::new(x, y);
+		if best.get(&pos).map(|(c, _)| *c < cost).unwrap_or(false) {
+			// Stale entry
+			continue;
+		}
+
+		let remaining = distance_to_goal(pos, goals);
+		if remaining == 0 {
+			found = Some(pos);
+			break;
+		}
+		if remaining < closest.0 {
+			closest = (remaining, pos);
+		}
+
+		ops += 1;
+		if ops >= options.max_ops {
+			break;
+		}
+
+		for next in neighbours(pos) {
+			let step = match rooms.cost(next, options) {
+				Some(step) => step,
+				None => continue,
+			};
+			let next_cost = cost + step;
+			if next_cost > options.max_cost {
+				continue;
+			}
+			if best
+				.get(&next)
+				.map(|(c, _)| *c <= next_cost)
+				.unwrap_or(false)
+			{
+				continue;
+			}
+			best.insert(next, (next_cost, Some(pos)));
+			open.push(Reverse((
+				next_cost + heuristic(next),
+				next_cost,
+				next.x,
+				next.y,
+			)));
+		}
+	}
+
+	let (end, incomplete) = match found {
+		Some(end) => (end, false),
+		None => (closest.1, true),
+	};
+
+	let mut path = Vec::new();
+	let mut current = end;
+	while let Some((_, Some(previous))) = best.get(&current) {
+		path.push(current);
+		current = *previous;
+	}
+	path.reverse();
+
+	SearchResult {
+		path,
+		cost: best.get(&end).map(|(c, _)| *c).unwrap_or(0),
+		ops,
+		incomplete,
+	}
+}
+
+/// Cost to reach every tile of a room from the nearest origin, `u32::MAX` where unreachable
+pub fn dijkstra_map(
+	terrain: &RoomTerrain,
+	costs: Option<&RoomCosts>,
+	origins: &[(u8, u8)],
+	options: &SearchOptions,
+) -> Vec<u32> {
+	let room = RoomCoord::new(0, 0);
+	let mut rooms = SearchRooms::new().with_terrain(room, terrain.clone());
+	if let Some(costs) = costs {
+		rooms = rooms.with_costs(room, costs.clone());
+	}
+
+	let mut distances = vec![u32::MAX; ROOM_AREA];
+	let mut open = BinaryHeap::new();
+	origins.iter().for_each(|(x, y)| {
+		distances[*y as usize * 50 + *x as usize] = 0;
+		open.push(Reverse((0, *x, *y)));
+	});
+
+	while let Some(Reverse((cost, x, y))) = open.pop() {
+		if distances[y as usize * 50 + x as usize] < cost {
+			continue;
+		}
+		let pos = WorldPos::from_local(room, x, y);
+		for next in neighbours(pos).filter(|next| next.room() == room) {
+			let step = match rooms.cost(next, options) {
+				Some(step) => step,
+				None => continue,
+			};
+			let (nx, ny) = next.local();
+			let index = ny as usize * 50 + nx as usize;
+			if cost + step < distances[index] {
+				distances[index] = cost + step;
+				open.push(Reverse((cost + step, nx, ny)));
+			}
+		}
+	}
+
+	distances
+}
+
+/// Chebyshev distance from every tile to the nearest wall or room edge, useful for finding open
+/// areas to place compact structure layouts
+pub fn distance_transform(terrain: &RoomTerrain) -> Vec<u8> {
+	let mut distances = vec![0u8; ROOM_AREA];
+	for y in 0..50u8 {
+		for x in 0..50u8 {
+			if terrain.get(x, y) != Terrain::Wall && x > 0 && y > 0 && x < 49 && y < 49 {
+				distances[y as usize * 50 + x as usize] = u8::MAX;
+			}
+		}
+	}
+
+	let index = |x: usize, y: usize| y * 50 + x;
+
+	// Forward pass over the top left neighbours
+	for y in 1..49 {
+		for x in 1..49 {
+			let current = distances[index(x, y)];
+			if current == 0 {
+				continue;
+			}
+			let smallest = [
+				distances[index(x - 1, y)],
+				distances[index(x - 1, y - 1)],
+				distances[index(x, y - 1)],
+				distances[index(x + 1, y - 1)],
+			]
+			.iter()
+			.copied()
+			.min()
+			.unwrap();
+			distances[index(x, y)] = current.min(smallest.saturating_add(1));
+		}
+	}
+
+	// Backward pass over the bottom right neighbours
+	for y in (1..49).rev() {
+		for x in (1..49).rev() {
+			let current = distances[index(x, y)];
+			if current == 0 {
+				continue;
+			}
+			let smallest = [
+				distances[index(x + 1, y)],
+				distances[index(x + 1, y + 1)],
+				distances[index(x, y + 1)],
+				distances[index(x - 1, y + 1)],
+			]
+			.iter()
+			.copied()
+			.min()
+			.unwrap();
+			distances[index(x, y)] = current.min(smallest.saturating_add(1));
+		}
+	}
+
+	distances
+}
+
+/// Breadth first walk outwards from the seeds over non-wall tiles, recording the number of steps
+/// to each tile. Tiles further than `max_depth` or unreachable are left as `u8::MAX`.
+pub fn flood_fill(terrain: &RoomTerrain, seeds: &[(u8, u8)], max_depth: u8) -> Vec<u8> {
+	let mut depths = vec![u8::MAX; ROOM_AREA];
+	let mut queue = VecDeque::new();
+
+	seeds.iter().for_each(|(x, y)| {
+		depths[*y as usize * 50 + *x as usize] = 0;
+		queue.push_back((*x, *y));
+	});
+
+	while let Some((x, y)) = queue.pop_front() {
+		let depth = depths[y as usize * 50 + x as usize];
+		if depth >= max_depth {
+			continue;
+		}
+		for (dx, dy) in &[
+			(-1, -1),
+			(0, -1),
+			(1, -1),
+			(-1, 0),
+			(1, 0),
+			(-1, 1),
+			(0, 1),
+			(1, 1),
+		] {
+			let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+			if !(0..50).contains(&nx) || !(0..50).contains(&ny) {
+				continue;
+			}
+			let (nx, ny) = (nx as u8, ny as u8);
+			let index = ny as usize * 50 + nx as usize;
+			if depths[index] == u8::MAX && terrain.get(nx, ny) != Terrain::Wall {
+				depths[index] = depth + 1;
+				queue.push_back((nx, ny));
+			}
+		}
+	}
+
+	depths
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const ROOM: RoomCoord = RoomCoord { x: 0, y: 0 };
+
+	fn at(x: u8, y: u8) -> WorldPos { WorldPos::from_local(ROOM, x, y) }
+
+	fn search_in(terrain: RoomTerrain, from: (u8, u8), to: (u8, u8), range: u32) -> SearchResult {
+		let rooms = SearchRooms::new().with_terrain(ROOM, terrain);
+		search(
+			at(from.0, from.1),
+			&[(at(to.0, to.1), range)],
+			&rooms,
+			&SearchOptions::default(),
+		)
+	}
+
+	#[test]
+	fn search_goes_around_walls() {
+		let terrain = RoomTerrain::from_rows(&[
+			"..........",
+			"....#.....",
+			"....#.....",
+			"....#.....",
+			"....#.....",
+			"..........",
+		]);
+		let result = search_in(terrain, (2, 3), (6, 3), 0);
+
+		assert!(!result.incomplete);
+		assert_eq!(result.cost, 8);
+		assert_eq!(result.path, vec![at(3, 4), at(4, 5), at(5, 4), at(6, 3)]);
+	}
+
+	#[test]
+	fn search_avoids_swamp_when_cheaper() {
+		let terrain = RoomTerrain::from_rows(&[
+			"..........",
+			"...~......",
+			"...~......",
+			"...~......",
+			"..........",
+		]);
+		let result = search_in(terrain.clone(), (2, 2), (4, 2), 0);
+
+		assert!(!result.incomplete);
+		assert_eq!(result.cost, 8);
+		assert_eq!(result.path.len(), 4);
+		assert!(result
+			.path
+			.iter()
+			.all(|pos| terrain.get(pos.local().0, pos.local().1) == Terrain::Plain));
+	}
+
+	#[test]
+	fn search_takes_roads_away_from_the_goal() {
+		let terrain = RoomTerrain::from_rows(&[]);
+		// The only road leaves westwards before looping back east to the goal
+		let mut costs = RoomCosts::default();
+		(5..=9).for_each(|x| costs.set(x, 25, 1));
+		(21..=24).for_each(|y| costs.set(5, y, 1));
+		(5..=20).for_each(|x| costs.set(x, 20, 1));
+		(21..=24).for_each(|y| costs.set(20, y, 1));
+		let options = SearchOptions {
+			plain_cost: 5,
+			swamp_cost: 25,
+			..Default::default()
+		};
+		let rooms = SearchRooms::new()
+			.with_terrain(ROOM, terrain.clone())
+			.with_costs(ROOM, costs.clone());
+
+		let result = search(at(10, 25), &[(at(20, 25), 0)], &rooms, &options);
+		let distances = dijkstra_map(&terrain, Some(&costs), &[(10, 25)], &options);
+		assert!(!result.incomplete);
+		assert_eq!(result.cost, distances[25 * 50 + 20]);
+	}
+
+	#[test]
+	fn search_stops_within_range() {
+		let result = search_in(RoomTerrain::from_rows(&[]), (1, 1), (5, 1), 2);
+
+		assert!(!result.incomplete);
+		assert_eq!(result.cost, 4);
+		assert_eq!(result.path.len(), 2);
+		assert_eq!(result.path.last().unwrap().range(&at(5, 1)), 2);
+	}
+
+	#[test]
+	fn search_marks_unreachable_goals_incomplete() {
+		let terrain = RoomTerrain::from_rows(&["......", "..###.", "..#.#.", "..###."]);
+		let result = search_in(terrain, (1, 1), (3, 2), 0);
+
+		assert!(result.incomplete);
+		assert_ne!(result.path.last(), Some(&at(3, 2)));
+	}
+
+	#[test]
+	fn dijkstra_map_costs() {
+		let terrain = RoomTerrain::from_rows(&["......", "...~..", "......", ".#...."]);
+		let distances = dijkstra_map(&terrain, None, &[(1, 1)], &SearchOptions::default());
+		let get = |x: usize, y: usize| distances[y * 50 + x];
+
+		assert_eq!(get(1, 1), 0);
+		assert_eq!(get(2, 1), 2);
+		// Every way onto the swamp ends with a swamp step
+		assert_eq!(get(3, 1), 12);
+		// Cheaper around the swamp than through it
+		assert_eq!(get(4, 1), 6);
+		assert_eq!(get(1, 3), u32::MAX);
+	}
+
+	#[test]
+	fn distance_transform_measures_to_walls_and_edges() {
+		let open = distance_transform(&RoomTerrain::from_rows(&[]));
+		assert_eq!(open[0], 0);
+		assert_eq!(open[50 + 1], 1);
+		assert_eq!(open[25 * 50 + 25], 24);
+		assert_eq!(open[24 * 50 + 24], 24);
+
+		let mut rows = vec![".........."; 10];
+		rows.push("..........#");
+		let walled = distance_transform(&RoomTerrain::from_rows(&rows));
+		assert_eq!(walled[10 * 50 + 10], 0);
+		assert_eq!(walled[10 * 50 + 12], 2);
+		assert_eq!(walled[11 * 50 + 11], 1);
+	}
+
+	#[test]
+	fn flood_fill_crosses_swamp_but_not_walls() {
+		let terrain = RoomTerrain::from_rows(&["#####....", "#.~.#....", "#...#....", "#####...."]);
+		let depths = flood_fill(&terrain, &[(1, 1)], u8::MAX);
+		let get = |x: usize, y: usize| depths[y * 50 + x];
+
+		assert_eq!(get(1, 1), 0);
+		assert_eq!(get(2, 1), 1);
+		assert_eq!(get(3, 2), 2);
+		assert_eq!(get(0, 0), u8::MAX);
+		assert_eq!(get(5, 1), u8::MAX);
+	}
+
+	#[test]
+	fn flood_fill_stops_at_max_depth() {
+		let depths = flood_fill(&RoomTerrain::from_rows(&[]), &[(10, 10)], 2);
+
+		assert_eq!(depths[10 * 50 + 12], 2);
+		assert_eq!(depths[10 * 50 + 13], u8::MAX);
+	}
+}
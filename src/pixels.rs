@@ -0,0 +1,32 @@
+use screeps::{constants::ReturnCode, Game};
+
+use num_traits::cast::FromPrimitive;
+
+use crate::{
+	config,
+	error::{Error, Result},
+	intel, log_info,
+	scheduler::bucket,
+};
+
+/// Turn a full bucket into a pixel when configured to, as long as the tick left enough of its
+/// budget unused and none of our rooms is under threat and may need the bucket to respond
+pub fn generate(remaining_cpu: f64) -> Result<()> {
+	let config = config::get().pixels;
+	if !config.enabled || bucket() < config.min_bucket {
+		return Ok(());
+	}
+
+	if remaining_cpu < config.reserve_cpu || intel::any_threat() {
+		return Ok(());
+	}
+
+	match ReturnCode::from_i8(Game::cpu().generate_pixel()).unwrap() {
+		ReturnCode::Ok => {
+			log_info!("generated a pixel");
+			Ok(())
+		}
+		ReturnCode::NotEnough => Ok(()),
+		x => Err(Error::UnhandledErrorCode(x)),
+	}
+}
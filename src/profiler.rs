@@ -0,0 +1,189 @@
+use wasm_bindgen::prelude::*;
+
+use once_cell::sync::Lazy;
+
+use screeps::Game;
+
+use std::{
+	collections::{HashMap, VecDeque},
+	sync::Mutex,
+};
+
+use crate::scheduler::cpu_used;
+
+/// Number of ticks samples are kept for
+const WINDOW: u32 = 100;
+
+#[derive(Clone, Copy, Default)]
+struct TickSample {
+	time: u32,
+	cpu: f64,
+	calls: u32,
+}
+
+#[derive(Default)]
+struct Series(VecDeque<TickSample>);
+
+impl Series {
+	fn record(&mut self, time: u32, cpu: f64) {
+		match self.0.back_mut() {
+			Some(sample) if sample.time == time => {
+				sample.cpu += cpu;
+				sample.calls += 1;
+			}
+			_ => self.0.push_back(TickSample {
+				time,
+				cpu,
+				calls: 1,
+			}),
+		}
+		while self
+			.0
+			.front()
+			.map(|sample| time.saturating_sub(sample.time) >= WINDOW)
+			.unwrap_or(false)
+		{
+			self.0.pop_front();
+		}
+	}
+
+	fn total(&self) -> (f64, u32, usize) {
+		let cpu = self.0.iter().map(|sample| sample.cpu).sum();
+		let calls = self.0.iter().map(|sample| sample.calls).sum();
+		(cpu, calls, self.0.len())
+	}
+}
+
+#[derive(Default)]
+struct Profiler {
+	enabled: bool,
+	sections: HashMap<String, Series>,
+	creeps: HashMap<String, Series>,
+}
+
+static PROFILER: Lazy<Mutex<Profiler>> = Lazy::new(|| Mutex::new(Profiler::default()));
+
+pub fn enabled() -> bool { PROFILER.lock().unwrap().enabled }
+
+/// Measure the CPU used by a section of code
+pub fn profile<T>(label: &str, f: impl FnOnce() -> T) -> T {
+	if !enabled() {
+		return f();
+	}
+	let start = cpu_used();
+	let result = f();
+	let used = cpu_used() - start;
+
+	PROFILER
+		.lock()
+		.unwrap()
+		.sections
+		.entry(label.to_string())
+		.or_default()
+		.record(Game::time(), used);
+
+	result
+}
+
+/// Measure a job being driven, attributing the CPU to both the job state and the creep
+pub fn profile_job<T>(creep: &str, label: &str, f: impl FnOnce() -> T) -> T {
+	if !enabled() {
+		return f();
+	}
+	let start = cpu_used();
+	let result = f();
+	let used = cpu_used() - start;
+
+	let time = Game::time();
+	let mut profiler = PROFILER.lock().unwrap();
+	profiler
+		.sections
+		.entry(format!("job:{}", label))
+		.or_default()
+		.record(time, used);
+	profiler
+		.creeps
+		.entry(creep.to_string())
+		.or_default()
+		.record(time, used);
+
+	result
+}
+
+/// Drop series which have had no samples for a whole window, such as those of dead creeps
+pub fn prune() {
+	let time = Game::time();
+	let stale = |series: &Series| {
+		series
+			.0
+			.back()
+			.map(|sample| time.saturating_sub(sample.time) >= WINDOW)
+			.unwrap_or(true)
+	};
+	let mut profiler = PROFILER.lock().unwrap();
+	profiler.sections.retain(|_, series| !stale(series));
+	profiler.creeps.retain(|_, series| !stale(series));
+}
+
+fn ranked(series: &HashMap<String, Series>, count: usize) -> Vec<(String, f64, u32, usize)> {
+	let mut rows = series
+		.iter()
+		.map(|(label, series)| {
+			let (cpu, calls, ticks) = series.total();
+			(label.clone(), cpu, calls, ticks)
+		})
+		.collect::<Vec<_>>();
+	rows.sort_by(|(_, a, ..), (_, b, ..)| b.partial_cmp(a).unwrap());
+	rows.truncate(count);
+	rows
+}
+
+#[wasm_bindgen(js_name = profilerEnable)]
+pub fn set_enabled(enabled: bool) {
+	let mut profiler = PROFILER.lock().unwrap();
+	profiler.enabled = enabled;
+	if !enabled {
+		profiler.sections.clear();
+		profiler.creeps.clear();
+	}
+}
+
+/// Sections and creeps ranked by total CPU over the window
+#[wasm_bindgen(js_name = profilerReport)]
+pub fn report(count: usize) -> String {
+	let profiler = PROFILER.lock().unwrap();
+	if !profiler.enabled {
+		return "Profiler is disabled".to_string();
+	}
+
+	let mut out = format!("CPU profile over the last {} ticks\n", WINDOW);
+	out.push_str(&format!(
+		"{:<40} {:>8} {:>8} {:>10} {:>10}\n",
+		"section", "calls", "total", "per call", "per tick"
+	));
+	for (label, cpu, calls, ticks) in ranked(&profiler.sections, count) {
+		out.push_str(&format!(
+			"{:<40} {:>8} {:>8.2} {:>10.3} {:>10.3}\n",
+			label,
+			calls,
+			cpu,
+			cpu / calls.max(1) as f64,
+			cpu / ticks.max(1) as f64
+		));
+	}
+
+	out.push_str(&format!(
+		"\n{:<40} {:>8} {:>10}\n",
+		"creep", "total", "per tick"
+	));
+	for (name, cpu, _, ticks) in ranked(&profiler.creeps, count) {
+		out.push_str(&format!(
+			"{:<40} {:>8.2} {:>10.3}\n",
+			name,
+			cpu,
+			cpu / ticks.max(1) as f64
+		));
+	}
+
+	out
+}
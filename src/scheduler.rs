@@ -0,0 +1,196 @@
+use once_cell::sync::Lazy;
+
+use screeps::Game;
+
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::{error::Result, log_debug};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Priority {
+	/// Always runs, even past the budget
+	Critical,
+	High,
+	Normal,
+	/// First to be deferred when the bucket drains
+	Low,
+}
+
+impl Priority {
+	/// Bucket required before tasks of this priority are allowed to run
+	fn min_bucket(&self) -> i32 {
+		match self {
+			Priority::Critical => 0,
+			Priority::High => 500,
+			Priority::Normal => 2000,
+			Priority::Low => 5000,
+		}
+	}
+}
+
+/// A subsystem run by the scheduler
+pub struct Task {
+	pub name: &'static str,
+	pub priority: Priority,
+	/// Run every this many ticks while CPU allows
+	pub frequency: u32,
+	/// Fraction of the tick budget the task is expected to stay within
+	pub share: f64,
+}
+
+pub const INTEL: Task = Task {
+	name: "intel",
+	priority: Priority::Low,
+	frequency: 5,
+	share: 0.05,
+};
+
+pub const MAINTENANCE: Task = Task {
+	name: "maintenance",
+	priority: Priority::Low,
+	frequency: 50,
+	share: 0.05,
+};
+
+/// Interrupts and job assignment, which keep creeps working and out of danger
+pub const STRATEGY: Task = Task {
+	name: "strategy",
+	priority: Priority::Critical,
+	frequency: 1,
+	share: 0.15,
+};
+
+pub const SPAWNING: Task = Task {
+	name: "spawning",
+	priority: Priority::High,
+	frequency: 1,
+	share: 0.05,
+};
+
+pub const CREEPS: Task = Task {
+	name: "creeps",
+	priority: Priority::Critical,
+	frequency: 1,
+	share: 0.6,
+};
+
+pub const MOVEMENT: Task = Task {
+	name: "movement",
+	priority: Priority::Critical,
+	frequency: 1,
+	share: 0.2,
+};
+
+pub const LINKS: Task = Task {
+	name: "links",
+	priority: Priority::High,
+	frequency: 1,
+	share: 0.02,
+};
+
+pub const STATS: Task = Task {
+	name: "stats",
+	priority: Priority::Low,
+	frequency: 1,
+	share: 0.05,
+};
+
+pub const VISUALS: Task = Task {
+	name: "visuals",
+	priority: Priority::Low,
+	frequency: 1,
+	share: 0.05,
+};
+
+pub const PIXELS: Task = Task {
+	name: "pixels",
+	priority: Priority::Low,
+	frequency: 1,
+	share: 0.0,
+};
+
+/// Tick a task last ran, kept across ticks so deferred tasks catch up once CPU recovers
+static LAST_RUN: Lazy<Mutex<HashMap<&'static str, u32>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub fn cpu_used() -> f64 { Game::cpu().get_used() }
+
+pub fn bucket() -> i32 { Game::cpu().bucket() }
+
+/// CPU this tick is allowed to spend, based on the account limit and how full the bucket is
+fn tick_budget(limit: f64, tick_limit: f64, bucket: i32) -> f64 {
+	let budget = match bucket {
+		b if b < 1000 => limit * 0.5,
+		b if b < 3000 => limit * 0.8,
+		b if b < 8000 => limit,
+		_ => limit * 1.5,
+	};
+	budget.min(tick_limit * 0.9)
+}
+
+pub struct Scheduler {
+	time: u32,
+	bucket: i32,
+	budget: f64,
+	deferred: Vec<&'static str>,
+}
+
+impl Scheduler {
+	pub fn start() -> Self {
+		let cpu = Game::cpu();
+		let bucket = cpu.bucket();
+		Scheduler {
+			time: Game::time(),
+			bucket,
+			budget: tick_budget(cpu.limit() as f64, cpu.tick_limit(), bucket),
+			deferred: Vec::new(),
+		}
+	}
+
+	pub fn budget(&self) -> f64 { self.budget }
+
+	pub fn remaining(&self) -> f64 { self.budget - cpu_used() }
+
+	/// CPU a task may use when it runs this tick
+	pub fn task_budget(&self, task: &Task) -> f64 { self.budget * task.share }
+
+	fn should_run(&self, task: &Task) -> bool {
+		if task.priority == Priority::Critical {
+			return true;
+		}
+		if self.bucket < task.priority.min_bucket() || self.remaining() <= 0.0 {
+			return false;
+		}
+		let last_run = LAST_RUN.lock().unwrap().get(task.name).copied();
+		match last_run {
+			Some(last_run) => self.time.saturating_sub(last_run) >= task.frequency,
+			None => true,
+		}
+	}
+
+	/// Run a task if its priority, frequency and the remaining budget allow it. Returns whether the
+	/// task ran.
+	pub fn run(&mut self, task: &Task, f: impl FnOnce() -> Result<()>) -> Result<bool> {
+		if !self.should_run(task) {
+			self.deferred.push(task.name);
+			return Ok(false);
+		}
+
+		LAST_RUN.lock().unwrap().insert(task.name, self.time);
+		let start = cpu_used();
+		f()?;
+		let used = cpu_used() - start;
+
+		if task.priority != Priority::Critical && used > self.task_budget(task) * 2.0 {
+			log_debug!(
+				"task {} used {:.2} CPU, budget {:.2}",
+				task.name,
+				used,
+				self.task_budget(task)
+			);
+		}
+
+		Ok(true)
+	}
+
+	pub fn deferred(&self) -> &[&'static str] { &self.deferred }
+}
@@ -0,0 +1,326 @@
+use wasm_bindgen::prelude::*;
+
+use js_sys::{Array, Object, Reflect};
+
+use once_cell::sync::Lazy;
+
+use screeps::Game;
+
+use std::{
+	collections::{BTreeMap, BTreeSet, HashMap},
+	ops::Range,
+	sync::Mutex,
+};
+
+use crate::{
+	codec,
+	error::{Error, Result},
+};
+
+/// Segments which can be active in one tick
+const MAX_ACTIVE: usize = 10;
+/// Segments which can be written in one tick
+const MAX_WRITES: usize = 10;
+/// Characters a segment can hold
+const SEGMENT_SIZE: usize = 100 * 1024;
+/// Characters set aside for the chunk header
+const HEADER_SIZE: usize = 32;
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(js_namespace = RawMemory, js_name = setActiveSegments)]
+	fn set_active_segments(ids: &Array);
+}
+
+/// Kinds of data kept in segments, each owning a fixed range of segment ids. Segments 90 to 99
+/// are left for raw data such as stats, which is written with `write_raw`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum SegmentKind {
+	Intel,
+	Plans,
+	Paths,
+}
+
+impl SegmentKind {
+	fn range(&self) -> Range<u8> {
+		match self {
+			SegmentKind::Intel => 0..10,
+			SegmentKind::Plans => 10..30,
+			SegmentKind::Paths => 30..40,
+		}
+	}
+
+	/// Whether payloads are compressed before being stored
+	fn compressed(&self) -> bool {
+		match self {
+			SegmentKind::Intel => true,
+			SegmentKind::Plans => true,
+			SegmentKind::Paths => false,
+		}
+	}
+}
+
+/// How a payload is stored in its segments
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Format {
+	/// UTF-8 stored as is
+	Text,
+	/// Bytes packed into a string
+	Packed,
+	/// Bytes compressed, then packed
+	Compressed,
+}
+
+impl Format {
+	fn marker(&self) -> char {
+		match self {
+			Format::Text => 'J',
+			Format::Packed => 'P',
+			Format::Compressed => 'Z',
+		}
+	}
+
+	fn from_marker(marker: char) -> Option<Format> {
+		match marker {
+			'J' => Some(Format::Text),
+			'P' => Some(Format::Packed),
+			'Z' => Some(Format::Compressed),
+			_ => None,
+		}
+	}
+}
+
+/// Header at the start of every chunk of a payload. The first chunk also records the format and
+/// chunk count, for example `Z3|4120|` followed by `4120|` on the other two.
+struct Header {
+	format: Format,
+	count: usize,
+	generation: u32,
+}
+
+impl Header {
+	fn encode(&self, index: usize) -> String {
+		if index == 0 {
+			format!(
+				"{}{}|{}|",
+				self.format.marker(),
+				self.count,
+				self.generation
+			)
+		}
+		else {
+			format!("{}|", self.generation)
+		}
+	}
+
+	/// Parse the header of the first chunk, returning the rest of it
+	fn parse_first(chunk: &str) -> Option<(Header, &str)> {
+		let format = Format::from_marker(chunk.chars().next()?)?;
+		let mut parts = chunk[1..].splitn(3, '|');
+		let count = parts.next()?.parse().ok()?;
+		let generation = parts.next()?.parse().ok()?;
+		Some((
+			Header {
+				format,
+				count,
+				generation,
+			},
+			parts.next()?,
+		))
+	}
+
+	/// Strip the header of a later chunk, checking it belongs to the same write
+	fn parse_rest<'a>(&self, chunk: &'a str) -> Option<&'a str> {
+		let mut parts = chunk.splitn(2, '|');
+		match parts.next()?.parse::<u32>() {
+			Ok(generation) if generation == self.generation => parts.next(),
+			_ => None,
+		}
+	}
+}
+
+#[derive(Default)]
+struct Manager {
+	/// Segments to activate next tick
+	requested: BTreeSet<u8>,
+	/// Writes not yet flushed, overwriting the segment contents. Parts of a payload are kept
+	/// together under its first segment, so they are all written in the same tick.
+	writes: BTreeMap<u8, Vec<(u8, String)>>,
+	/// Chunk counts last seen for each kind, so every chunk can be requested at once
+	counts: HashMap<SegmentKind, usize>,
+}
+
+static MANAGER: Lazy<Mutex<Manager>> = Lazy::new(|| Mutex::new(Manager::default()));
+
+fn raw_segments() -> Object {
+	let raw_memory = Reflect::get(&js_sys::global(), &JsValue::from("RawMemory")).unwrap();
+	Object::from(Reflect::get(&raw_memory, &JsValue::from("segments")).unwrap())
+}
+
+/// Contents of a segment, from pending writes or if it is active this tick
+fn segment(manager: &Manager, segments: &Object, id: u8) -> Option<String> {
+	let pending = manager
+		.writes
+		.values()
+		.flatten()
+		.find(|(pending, _)| *pending == id);
+	match pending {
+		Some((_, data)) => Some(data.clone()),
+		None => Reflect::get(segments, &JsValue::from(id))
+			.ok()
+			.and_then(|value| value.as_string()),
+	}
+}
+
+/// Ask for a kind's segments to be active next tick, ahead of reading it
+pub fn request(kind: SegmentKind) {
+	let mut manager = MANAGER.lock().unwrap();
+	let count = manager.counts.get(&kind).copied().unwrap_or(1);
+	let ids = kind.range().take(count);
+	manager.requested.extend(ids);
+}
+
+/// Read a payload, or `None` while its segments are still being activated
+pub fn read(kind: SegmentKind) -> Result<Option<Vec<u8>>> {
+	let mut manager = MANAGER.lock().unwrap();
+	let segments = raw_segments();
+	let mut ids = kind.range();
+
+	let first = match segment(&manager, &segments, ids.start) {
+		Some(first) => first,
+		None => {
+			manager.requested.insert(ids.start);
+			return Ok(None);
+		}
+	};
+	if first.is_empty() {
+		return Ok(Some(Vec::new()));
+	}
+	let (header, body) = Header::parse_first(&first)
+		.ok_or_else(|| Error::Segment(format!("{:?} has an unreadable header", kind)))?;
+	manager.counts.insert(kind, header.count);
+
+	ids.next();
+	let rest = ids
+		.take(header.count.saturating_sub(1))
+		.map(|id| (id, segment(&manager, &segments, id)))
+		.collect::<Vec<_>>();
+	let missing = rest
+		.iter()
+		.filter(|(_, chunk)| chunk.is_none())
+		.map(|(id, _)| *id)
+		.collect::<Vec<_>>();
+	if !missing.is_empty() {
+		manager.requested.extend(missing);
+		return Ok(None);
+	}
+
+	let mut data = body.to_string();
+	for (id, chunk) in rest {
+		let chunk = header
+			.parse_rest(chunk.as_deref().unwrap())
+			.ok_or_else(|| Error::Segment(format!("segment {} is from another write", id)))?;
+		data.push_str(chunk);
+	}
+
+	let bytes = match header.format {
+		Format::Text => Some(data.into_bytes()),
+		Format::Packed => codec::unpack(&data),
+		Format::Compressed => codec::unpack(&data).and_then(|packed| codec::decompress(&packed)),
+	};
+	bytes
+		.map(Some)
+		.ok_or_else(|| Error::Segment(format!("{:?} could not be decoded", kind)))
+}
+
+/// Store a payload, splitting it over as many of the kind's segments as needed. A payload can use
+/// at most as many segments as can be written in one tick.
+pub fn write(kind: SegmentKind, data: &[u8]) -> Result<()> {
+	let (format, data) = if kind.compressed() {
+		(Format::Compressed, codec::pack(&codec::compress(data)))
+	}
+	else {
+		match std::str::from_utf8(data) {
+			Ok(text) => (Format::Text, text.to_string()),
+			Err(_) => (Format::Packed, codec::pack(data)),
+		}
+	};
+
+	let chars = data.chars().collect::<Vec<_>>();
+	let chunks = chars.chunks(SEGMENT_SIZE - HEADER_SIZE).collect::<Vec<_>>();
+	let range = kind.range();
+	let available = range.len().min(MAX_WRITES);
+	if chunks.len() > available {
+		return Err(Error::Segment(format!(
+			"{:?} needs {} segments but can only use {}",
+			kind,
+			chunks.len(),
+			available
+		)));
+	}
+
+	let header = Header {
+		format,
+		count: chunks.len().max(1),
+		generation: Game::time(),
+	};
+
+	let mut manager = MANAGER.lock().unwrap();
+	manager.counts.insert(kind, header.count);
+	let mut parts = range
+		.clone()
+		.zip(chunks)
+		.enumerate()
+		.map(|(index, (id, chunk))| {
+			let mut segment = header.encode(index);
+			segment.extend(chunk);
+			(id, segment)
+		})
+		.collect::<Vec<_>>();
+	if parts.is_empty() {
+		parts.push((range.start, header.encode(0)));
+	}
+	manager.writes.insert(range.start, parts);
+	Ok(())
+}
+
+/// Store a string as is, for data read by external tools
+pub fn write_raw(id: u8, data: String) {
+	MANAGER.lock().unwrap().writes.insert(id, vec![(id, data)]);
+}
+
+/// Write pending payloads and set the segments requested active for next tick. Called once at
+/// the end of each tick; payloads over the per tick limits are carried over whole.
+pub fn flush() {
+	let mut manager = MANAGER.lock().unwrap();
+
+	let segments = raw_segments();
+	let mut written = 0;
+	let due = manager
+		.writes
+		.iter()
+		.take_while(|(_, parts)| {
+			written += parts.len();
+			written <= MAX_WRITES
+		})
+		.map(|(first, _)| *first)
+		.collect::<Vec<_>>();
+	for first in due {
+		for (id, data) in manager.writes.remove(&first).unwrap() {
+			Reflect::set(&segments, &JsValue::from(id), &JsValue::from(data)).unwrap();
+		}
+	}
+
+	let active = manager
+		.requested
+		.iter()
+		.take(MAX_ACTIVE)
+		.copied()
+		.collect::<Vec<_>>();
+	active.iter().for_each(|id| {
+		manager.requested.remove(id);
+	});
+	if !active.is_empty() {
+		set_active_segments(&active.into_iter().map(JsValue::from).collect());
+	}
+}
@@ -0,0 +1,290 @@
+use wasm_bindgen::prelude::*;
+
+use js_sys::{Array, Reflect};
+
+use serde::{Deserialize, Serialize};
+
+use once_cell::sync::Lazy;
+
+use screeps::{
+	constants::{Find, ResourceType},
+	objects::{Creep, Room, StructureSpawn},
+	Game, Source,
+};
+
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::{
+	colony::Colony,
+	config,
+	error::Result,
+	jobs::JobKind,
+	scheduler::{bucket, cpu_used},
+	segments,
+	structures::CreepState,
+	util::{room_find, room_name},
+};
+
+const EVENT_BUILD: u32 = 4;
+const EVENT_HARVEST: u32 = 5;
+const EVENT_REPAIR: u32 = 7;
+const EVENT_UPGRADE_CONTROLLER: u32 = 9;
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(method, js_name = getEventLog)]
+	fn get_event_log(this: &Room) -> Array;
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatsConfig {
+	pub enabled: bool,
+	/// Ticks between writes to the segment
+	pub interval: u32,
+	pub segment: u8,
+}
+
+impl Default for StatsConfig {
+	fn default() -> Self {
+		StatsConfig {
+			enabled: true,
+			interval: 10,
+			segment: 99,
+		}
+	}
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Progress {
+	level: u32,
+	progress: f64,
+	progress_total: f64,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CpuStats {
+	used: f64,
+	limit: u32,
+	bucket: i32,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RoomStats {
+	energy_available: u32,
+	energy_capacity_available: u32,
+	storage_energy: u32,
+	controller: Progress,
+	/// Controller points gained per tick over the sampling interval
+	controller_progress_rate: f64,
+	/// Energy harvested per tick, averaged over the ticks sampled in the interval
+	energy_income: f64,
+	/// Energy spent on building, repairing and upgrading per tick, averaged over the ticks sampled
+	/// in the interval
+	energy_spend: f64,
+	creeps_by_build: HashMap<String, u16>,
+	creeps_by_job: HashMap<String, u16>,
+}
+
+/// Layout written to the stats segment. Field names are kept stable for dashboard exporters.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Stats {
+	time: u32,
+	cpu: CpuStats,
+	gcl: Progress,
+	gpl: Progress,
+	creeps: u32,
+	creeps_by_build: HashMap<String, u16>,
+	creeps_by_job: HashMap<String, u16>,
+	rooms: HashMap<String, RoomStats>,
+}
+
+#[derive(Default)]
+struct RoomTotals {
+	harvested: u32,
+	spent: u32,
+	/// Ticks whose event log was read, which is fewer than the interval when the task is skipped
+	sampled: u32,
+	last_progress: Option<u32>,
+}
+
+#[derive(Default)]
+struct Collector {
+	last_write: u32,
+	rooms: HashMap<String, RoomTotals>,
+}
+
+static COLLECTOR: Lazy<Mutex<Collector>> = Lazy::new(|| Mutex::new(Collector::default()));
+
+fn event_number(event: &JsValue, key: &str) -> u32 {
+	Reflect::get(event, &JsValue::from(key))
+		.ok()
+		.and_then(|value| value.as_f64())
+		.unwrap_or(0.0) as u32
+}
+
+/// Energy harvested and spent in a room this tick, read from its event log. Harvest events also
+/// cover minerals, so only those from a source count as income.
+fn room_events(room: &Room) -> (u32, u32) {
+	let sources = room_find(room, Find::Sources)
+		.iter()
+		.filter_map(|source| Source::from(source).id().as_string())
+		.collect::<Vec<_>>();
+	let from_source = |data: &JsValue| {
+		Reflect::get(data, &JsValue::from("targetId"))
+			.ok()
+			.and_then(|id| id.as_string())
+			.map(|id| sources.contains(&id))
+			.unwrap_or(false)
+	};
+
+	get_event_log(room)
+		.iter()
+		.fold((0, 0), |(harvested, spent), event| {
+			let data = Reflect::get(&event, &JsValue::from("data")).unwrap_or(JsValue::UNDEFINED);
+			match event_number(&event, "event") {
+				EVENT_HARVEST if from_source(&data) => {
+					(harvested + event_number(&data, "amount"), spent)
+				}
+				EVENT_BUILD | EVENT_REPAIR | EVENT_UPGRADE_CONTROLLER => {
+					(harvested, spent + event_number(&data, "energySpent"))
+				}
+				_ => (harvested, spent),
+			}
+		})
+}
+
+/// Accumulate this tick's metrics and write a sample to the stats segment when due
+pub fn collect(
+	creep_pairs: &[(Creep, CreepState)],
+	rooms: &[Room],
+	spawns: &[StructureSpawn],
+) -> Result<()> {
+	let config = config::with(|config| config.stats.clone());
+	if !config.enabled {
+		return Ok(());
+	}
+
+	let colonies = Colony::gather(creep_pairs, rooms, spawns);
+	let time = Game::time();
+	let mut collector = COLLECTOR.lock().unwrap();
+
+	colonies.iter().for_each(|colony| {
+		let (harvested, spent) = room_events(&colony.room);
+		let totals = collector.rooms.entry(colony.name.clone()).or_default();
+		totals.harvested += harvested;
+		totals.spent += spent;
+		totals.sampled += 1;
+	});
+
+	let elapsed = time.saturating_sub(collector.last_write);
+	if elapsed < config.interval {
+		return Ok(());
+	}
+
+	let mut stats = Stats {
+		time,
+		creeps: creep_pairs.len() as u32,
+		..Default::default()
+	};
+
+	let cpu = Game::cpu();
+	stats.cpu = CpuStats {
+		used: cpu_used(),
+		limit: cpu.limit(),
+		bucket: bucket(),
+	};
+
+	let gcl = Game::gcl();
+	stats.gcl = Progress {
+		level: gcl.level(),
+		progress: gcl.progress(),
+		progress_total: gcl.progress_total(),
+	};
+	let gpl = Game::gpl();
+	stats.gpl = Progress {
+		level: gpl.level(),
+		progress: gpl.progress(),
+		progress_total: gpl.progress_total(),
+	};
+
+	// Report every job, so graphs show a job dropping to zero creeps
+	stats.creeps_by_job = JobKind::ALL
+		.iter()
+		.map(|job| (job.name().to_string(), 0))
+		.collect();
+	creep_pairs.iter().for_each(|(_, state)| {
+		*stats
+			.creeps_by_build
+			.entry(state.build.to_string())
+			.or_insert(0) += 1;
+		*stats
+			.creeps_by_job
+			.entry(state.job.name().to_string())
+			.or_insert(0) += 1;
+	});
+
+	for colony in &colonies {
+		let room = &colony.room;
+		let controller = room.controller().unwrap();
+		let totals = collector.rooms.entry(colony.name.clone()).or_default();
+
+		let progress = controller.progress();
+		let sampled = totals.sampled.max(1) as f64;
+		let progress_rate = match totals.last_progress {
+			Some(last) if progress >= last => (progress - last) as f64 / elapsed as f64,
+			_ => 0.0,
+		};
+
+		stats.rooms.insert(
+			room_name(room),
+			RoomStats {
+				energy_available: room.energy_available(),
+				energy_capacity_available: room.energy_capacity_available(),
+				storage_energy: room
+					.storage()
+					.map(|storage| {
+						storage
+							.store()
+							.get_used_capacity(Some(ResourceType::Energy))
+					})
+					.unwrap_or(0),
+				controller: Progress {
+					level: colony.level() as u32,
+					progress: progress as f64,
+					progress_total: controller.progress_total() as f64,
+				},
+				controller_progress_rate: progress_rate,
+				energy_income: totals.harvested as f64 / sampled,
+				energy_spend: totals.spent as f64 / sampled,
+				creeps_by_build: colony
+					.creeps_by_build
+					.iter()
+					.map(|(build, count)| (build.to_string(), *count))
+					.collect(),
+				creeps_by_job: JobKind::ALL
+					.iter()
+					.map(|job| {
+						let count = colony.creeps_by_job.get(job).copied().unwrap_or(0);
+						(job.name().to_string(), count)
+					})
+					.collect(),
+			},
+		);
+
+		totals.harvested = 0;
+		totals.spent = 0;
+		totals.sampled = 0;
+		totals.last_progress = Some(progress);
+	}
+
+	collector.last_write = time;
+	drop(collector);
+
+	segments::write_raw(config.segment, serde_json::to_string(&stats)?);
+	Ok(())
+}
@@ -2,19 +2,22 @@ use wasm_bindgen::prelude::*;
 
 use once_cell::sync::Lazy;
 
-use screeps::{
-	constants::ResourceType,
-	objects::{ConstructionSite, Creep, Flag, Room, Structure, StructureSpawn},
-};
+use screeps::{constants::ReturnCode, objects::Creep};
 
 use std::collections::HashMap;
 
 use crate::{
 	builds::CreepBuild,
+	colony::Colony,
+	context::TickContext,
+	economy,
 	error::{Error, Result},
-	jobs::{CreepJob, Job},
+	interrupts, lifecycle, log_debug, log_warn,
+	logging::Context,
+	market,
+	profiler::{self, profile, profile_job},
 	structures::CreepState,
-	util::{log, spawn_creep},
+	util::{room_name, spawn_creep},
 };
 
 static SPAWNER_TIERS: Lazy<Vec<(u8, u32, HashMap<CreepBuild, u16>)>> = Lazy::new(|| {
@@ -41,85 +44,69 @@ static SPAWNER_TIERS: Lazy<Vec<(u8, u32, HashMap<CreepBuild, u16>)>> = Lazy::new
 	ordered
 });
 
+/// Builds, with their proportions, the colony's spawner currently produces
+pub fn recipe(colony: &Colony) -> &'static HashMap<CreepBuild, u16> {
+	let room_level = colony.level();
+	let room_energy_capacity = colony.room.energy_capacity_available();
+
+	let (_, _, recipe) = SPAWNER_TIERS
+		.iter()
+		.find(|(min_level, max_cost, _)| {
+			room_level >= *min_level && room_energy_capacity >= *max_cost
+		})
+		.unwrap();
+	recipe
+}
+
+/// Spawn for every colony. Kept apart from the creep strategy so it can be deferred when CPU runs
+/// short without leaving creeps idle or in danger.
+pub fn execute_spawning(
+	ctx: &TickContext,
+	creep_pairs: &[(Creep, CreepState)],
+	colonies: &[Colony],
+) -> Result<()> {
+	for colony in colonies {
+		profile("spawner_strategy", || {
+			spawner_strategy(ctx, creep_pairs, colony)
+		})?;
+	}
+
+	Ok(())
+}
+
 pub fn execute_strategy(
-	construction_sites: &[ConstructionSite],
+	ctx: &TickContext,
 	creep_pairs: &mut [(Creep, CreepState)],
-	flags: &[Flag],
-	rooms: &[Room],
-	spawns: &[StructureSpawn],
-	structures: &[Structure],
+	colonies: &[Colony],
 ) -> Result<()> {
 	// Top level strategy dispatch
 
-	let mut creeps_by_build = HashMap::<_, u16>::new();
-	let mut creeps_by_job = HashMap::<_, u16>::new();
-
-	creep_pairs.iter().for_each(|(_, state)| {
-		creeps_by_build
-			.entry(state.build.clone())
-			.and_modify(|x| *x += 1)
-			.or_insert(1);
-		creeps_by_job
-			.entry(state.job.clone())
-			.and_modify(|x| *x += 1)
-			.or_insert(1);
-	});
+	economy::update(colonies);
 
-	spawner_strategy(
-		construction_sites,
-		creep_pairs,
-		flags,
-		rooms,
-		spawns,
-		structures,
-		&creeps_by_build,
-		&creeps_by_job,
-	)?;
-
-	creep_strategy(
-		construction_sites,
-		creep_pairs,
-		flags,
-		rooms,
-		spawns,
-		structures,
-		&creeps_by_build,
-		&creeps_by_job,
-	)?;
+	profile("creep_strategy", || {
+		creep_strategy(ctx, creep_pairs, colonies)
+	})?;
 
 	Ok(())
 }
 
 fn spawner_strategy(
-	construction_sites: &[ConstructionSite],
-	creep_pairs: &mut [(Creep, CreepState)],
-	flags: &[Flag],
-	rooms: &[Room],
-	spawns: &[StructureSpawn],
-	structures: &[Structure],
-	creeps_by_build: &HashMap<CreepBuild, u16>,
-	creeps_by_job: &HashMap<CreepJob, u16>,
+	ctx: &TickContext,
+	creep_pairs: &[(Creep, CreepState)],
+	colony: &Colony,
 ) -> Result<()> {
-	spawns.iter().for_each(|spawner| {
-		let room = spawner.room().unwrap();
-		let controller = room.controller();
-		let room_level = controller.unwrap().level();
+	// Creeps about to die are not counted, so their replacements are ready in time
+	let mut creeps_by_build = lifecycle::lasting_creeps(creep_pairs, colony);
+	let population_target = colony.population_target();
 
-		let room_energy_capacity = room.energy_capacity_available();
-		let room_energy_available = room.energy_available();
+	let room_energy_available = colony.room.energy_available();
 
-		let creep_count = creep_pairs.len();
+	let recipe = recipe(colony);
 
-		let (_, _, recipe) = SPAWNER_TIERS
-			.iter()
-			.find(|(min_level, max_cost, _)| {
-				room_level >= *min_level && room_energy_capacity >= *max_cost
-			})
-			.unwrap();
+	let total_in_recipe = recipe.iter().map(|(_, proportion)| proportion).sum::<u16>();
 
-		let total_in_recipe = recipe.iter().map(|(_, proportion)| proportion).sum::<u16>();
-
-		let total_in_world = creeps_by_build
+	for spawner in &colony.spawns {
+		let total_in_colony = creeps_by_build
 			.iter()
 			.filter(|(build, _)| recipe.contains_key(build))
 			.map(|(_, count)| count)
@@ -128,117 +115,88 @@ fn spawner_strategy(
 		let chosen = recipe
 			.iter()
 			.map(|(build, proportion)| {
-				let world_proportion =
-					*creeps_by_build.get(build).unwrap_or(&0) as f32 / total_in_world as f32;
+				let colony_proportion =
+					*creeps_by_build.get(build).unwrap_or(&0) as f32 / total_in_colony as f32;
 
 				let wanted_proportion = *proportion as f32 / total_in_recipe as f32;
 
-				(world_proportion / wanted_proportion, build)
+				(colony_proportion / wanted_proportion, build)
 			})
 			.min_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap());
 
-		if let Some((_, build)) = chosen {
-			match room_level {
-				1 => {
-					if total_in_world < 5 {
-						spawn_creep(spawner, build);
-					}
-				}
-				2 => {
-					if total_in_world < 10 {
-						spawn_creep(spawner, build);
-					}
-				}
-				3 => {
-					if total_in_world < 10 {
-						spawn_creep(spawner, build);
-					}
-				}
-
-				_ => {
-					if total_in_world < 10 {
-						spawn_creep(spawner, build);
-					}
-				}
+		// Dedicated builds are only spawned once the recipe is filled
+		let dedicated = CreepBuild::ALL.iter().find(|build| {
+			let count = *creeps_by_build.get(build).unwrap_or(&0);
+			colony
+				.dedicated_target(ctx, build)
+				.map(|target| count < target)
+				.unwrap_or(false)
+		});
+
+		let build = match chosen {
+			Some((_, build)) if total_in_colony < population_target => Some(build),
+			Some(_) => dedicated,
+			None => None,
+		};
+		// Count what this spawn started, so the next spawn picks from the updated population
+		if let Some(build) = build {
+			if spawn_creep(spawner, build) == ReturnCode::Ok {
+				*creeps_by_build.entry(build.clone()).or_insert(0) += 1;
 			}
 		}
-	});
+	}
 
 	Ok(())
 }
 
 fn creep_strategy(
-	construction_sites: &[ConstructionSite],
+	ctx: &TickContext,
 	creep_pairs: &mut [(Creep, CreepState)],
-	flags: &[Flag],
-	rooms: &[Room],
-	spawns: &[StructureSpawn],
-	structures: &[Structure],
-	creeps_by_build: &HashMap<CreepBuild, u16>,
-	creeps_by_job: &HashMap<CreepJob, u16>,
+	colonies: &[Colony],
 ) -> Result<()> {
-	creep_pairs
-		.iter_mut()
-		.for_each(|(creep, state)| match &state.job {
-			CreepJob::None => match &state.build {
-				CreepBuild::Worker1_1 | CreepBuild::Worker2_1 | CreepBuild::Worker2_2 => {
-					let store = creep.store();
-
-					let energy_free_capacity = store.get_free_capacity(Some(ResourceType::Energy));
-					let energy_used_capacity = store.get_used_capacity(Some(ResourceType::Energy));
-					if energy_free_capacity >= energy_used_capacity {
-						state.job = CreepJob::Harvest(Default::default());
-					}
-					else {
-						state.job = CreepJob::DistributeEnergy(Default::default());
-					}
-				}
-			},
-			CreepJob::Harvest(job_state) => {
-				if job_state.finished() {
-					state.job = CreepJob::None
-				}
-			}
-			CreepJob::DistributeEnergy(job_state) => {
-				if job_state.finished() {
-					state.job = CreepJob::None
-				}
-			}
-		});
+	// Urgent work first, then release creeps from finished jobs, send those due to the spawn and
+	// match the rest to the colonies' offers
+	interrupts::apply(ctx, creep_pairs, colonies);
+
+	creep_pairs.iter_mut().for_each(|(_, state)| {
+		if state.job.finished() {
+			lifecycle::note_refusal(state);
+			interrupts::finish(state);
+		}
+	});
+
+	lifecycle::apply(ctx, creep_pairs, colonies);
+
+	market::assign(ctx, creep_pairs, colonies);
 
 	Ok(())
 }
 
-pub fn drive_creeps(
-	construction_sites: &[ConstructionSite],
-	creep_pairs: &mut [(Creep, CreepState)],
-	flags: &[Flag],
-	rooms: &[Room],
-	spawns: &[StructureSpawn],
-	structures: &[Structure],
-) -> Result<()> {
+pub fn drive_creeps(ctx: &TickContext, creep_pairs: &mut [(Creep, CreepState)]) -> Result<()> {
 	for (creep, state) in creep_pairs {
-		let res = match &mut state.job {
-			CreepJob::None => Ok(()),
-			CreepJob::Harvest(job_state) => {
-				job_state.drive(creep, construction_sites, flags, rooms, spawns, structures)
-			}
-			CreepJob::DistributeEnergy(job_state) => {
-				job_state.drive(creep, construction_sites, flags, rooms, spawns, structures)
-			}
+		let name = creep.name().as_string().unwrap();
+		// Labels are only needed for the profiler, or to report an error. The state driven is
+		// kept so an error names it rather than the state the job moved on to.
+		let driven = (state.job.kind(), state.job.state_name());
+		let label = if profiler::enabled() {
+			Some(state.job.label())
+		}
+		else {
+			None
+		};
+		let res = match &label {
+			Some(label) => profile_job(&name, label, || state.job.drive(creep, ctx)),
+			None => state.job.drive(creep, ctx),
 		};
 
 		match res {
 			Ok(_) => (),
 			Err(e) => {
-				let pos = creep.pos().unwrap();
-				log(format!(
-					"Error while driving creep at [{}, {}]: {}\nState: {:?}",
-					pos.x(),
-					pos.y(),
-					e,
-					state
-				));
+				let label = label.unwrap_or_else(|| driven.0.label(driven.1));
+				let room = creep.room().as_ref().map(room_name);
+				let context = Context::creep(name, room);
+				log_warn!(ctx: context, "error while driving {}: {}", label, e);
+				log_debug!(ctx: context, "state: {:?}", state);
 			}
 		}
 	}
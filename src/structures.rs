@@ -11,6 +11,7 @@ use std::{collections::HashMap, iter::repeat};
 use crate::{
 	builds::CreepBuild,
 	error::{Error, Result},
+	interrupts::Interrupt,
 	jobs::CreepJob,
 };
 
@@ -40,6 +41,9 @@ impl CreepParts {
 			.sum()
 	}
 
+	/// Number of body parts
+	pub fn count(&self) -> u32 { self.0.values().map(|count| *count as u32).sum() }
+
 	pub fn to_array(&self) -> Array {
 		self.0
 			.iter()
@@ -54,10 +58,30 @@ impl CreepParts {
 pub struct CreepState {
 	pub build: CreepBuild,
 	pub job: CreepJob,
+	#[serde(default)]
+	pub home: String,
+	/// Urgent work the current job was started for
+	#[serde(default)]
+	pub interrupt: Option<Interrupt>,
+	/// Job to resume once the interrupt is dealt with
+	#[serde(default)]
+	pub suspended: Option<CreepJob>,
+	/// Tick a spawn last turned the creep away from renewing
+	#[serde(default)]
+	pub renew_refused: Option<u32>,
 }
 
 impl CreepState {
-	pub fn new(build: CreepBuild, job: CreepJob) -> Self { CreepState { build, job } }
+	pub fn new(build: CreepBuild, job: CreepJob, home: String) -> Self {
+		CreepState {
+			build,
+			job,
+			home,
+			interrupt: None,
+			suspended: None,
+			renew_refused: None,
+		}
+	}
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
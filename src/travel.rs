@@ -0,0 +1,202 @@
+use wasm_bindgen::{prelude::*, JsCast};
+
+use js_sys::{Array, JsString, Object, Reflect};
+
+use once_cell::sync::Lazy;
+
+use screeps::{objects::RoomPosition, Game};
+
+use std::{
+	collections::{HashMap, HashSet},
+	sync::Mutex,
+};
+
+use crate::{
+	coords::WorldPos,
+	cost_matrix::{room_costs, CostMatrixBuilder, MatrixOptions},
+	error::{Error, Result},
+	intel,
+	util::{room_position, world_pos},
+};
+
+/// Ticks a room level route is trusted before being recomputed with fresh intel
+const ROUTE_TTL: u32 = 500;
+const MAX_OPS_PER_ROOM: u32 = 2000;
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(js_namespace = ["Game", "map"], js_name = findRoute)]
+	fn find_route_raw(from: &JsString, to: &JsString, options: &Object) -> JsValue;
+
+	#[wasm_bindgen(js_namespace = PathFinder, js_name = search)]
+	fn path_finder_search(origin: &RoomPosition, goal: &Object, options: &Object) -> Object;
+}
+
+struct CachedRoute {
+	rooms: Vec<String>,
+	created: u32,
+}
+
+static ROUTES: Lazy<Mutex<HashMap<(String, String), CachedRoute>>> =
+	Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn set(object: &Object, key: &str, value: &JsValue) {
+	Reflect::set(object, &JsValue::from(key), value).unwrap();
+}
+
+/// Rooms to pass through to get from one room to another, including both ends. Avoids rooms the
+/// intel database marks as hostile and prefers highways.
+pub fn find_route(from: &str, to: &str) -> Result<Vec<String>> {
+	if from == to {
+		return Ok(vec![from.to_string()]);
+	}
+
+	let key = (from.to_string(), to.to_string());
+	let time = Game::time();
+
+	if let Some(cached) = ROUTES.lock().unwrap().get(&key) {
+		if time.saturating_sub(cached.created) < ROUTE_TTL {
+			return Ok(cached.rooms.clone());
+		}
+	}
+
+	let callback = Closure::wrap(Box::new(|room: JsString, _from: JsString| -> f64 {
+		let room = room.as_string().unwrap();
+		intel::route_cost(&room).unwrap_or(f64::INFINITY)
+	}) as Box<dyn FnMut(JsString, JsString) -> f64>);
+
+	let options = Object::new();
+	set(&options, "routeCallback", callback.as_ref());
+
+	let result = find_route_raw(&JsString::from(from), &JsString::from(to), &options);
+
+	let steps = result.dyn_into::<Array>().map_err(|_| Error::NoneFound)?;
+	let rooms = std::iter::once(from.to_string())
+		.chain(steps.iter().filter_map(|step| {
+			Reflect::get(&step, &JsValue::from("room"))
+				.ok()
+				.and_then(|r| r.as_string())
+		}))
+		.collect::<Vec<_>>();
+
+	ROUTES.lock().unwrap().insert(
+		key,
+		CachedRoute {
+			rooms: rooms.clone(),
+			created: time,
+		},
+	);
+
+	Ok(rooms)
+}
+
+/// Tile level path restricted to the rooms of the room level route, using the cached cost matrix
+/// of each room. Tiles in `blocked` are treated as impassable, which is used to path around creeps.
+pub fn find_path(
+	from: WorldPos,
+	to: WorldPos,
+	range: u32,
+	blocked: &[WorldPos],
+) -> Result<Vec<WorldPos>> {
+	let route = find_route(&from.room().to_string(), &to.room().to_string())?;
+	let allowed = route.iter().cloned().collect::<HashSet<_>>();
+	let blocked = blocked.to_vec();
+
+	let callback = Closure::wrap(Box::new(move |room: JsString| -> JsValue {
+		let room = room.as_string().unwrap();
+		if !allowed.contains(&room) {
+			return JsValue::FALSE;
+		}
+		let costs = room_costs(&room, MatrixOptions::default()).unwrap_or_default();
+		CostMatrixBuilder::from_costs(costs)
+			.blocked(&room, &blocked)
+			.build()
+			.to_js()
+			.into()
+	}) as Box<dyn FnMut(JsString) -> JsValue>);
+
+	let goal = Object::new();
+	set(&goal, "pos", &room_position(to));
+	set(&goal, "range", &JsValue::from(range));
+
+	let options = Object::new();
+	set(&options, "roomCallback", callback.as_ref());
+	set(&options, "plainCost", &JsValue::from(2));
+	set(&options, "swampCost", &JsValue::from(10));
+	set(
+		&options,
+		"maxOps",
+		&JsValue::from(MAX_OPS_PER_ROOM * route.len() as u32),
+	);
+	set(&options, "maxRooms", &JsValue::from(route.len() as u32 + 1));
+
+	let result = path_finder_search(&room_position(from), &goal, &options);
+
+	let incomplete = Reflect::get(&result, &JsValue::from("incomplete"))
+		.ok()
+		.and_then(|x| x.as_bool())
+		.unwrap_or(true);
+	let path = Reflect::get(&result, &JsValue::from("path"))
+		.map_err(|_| Error::NoneFound)?
+		.dyn_into::<Array>()
+		.map_err(|_| Error::NoneFound)?;
+
+	if incomplete && path.length() == 0 {
+		return Err(Error::NoneFound);
+	}
+
+	Ok(path
+		.iter()
+		.map(RoomPosition::from)
+		.filter_map(|pos| world_pos(&pos))
+		.collect())
+}
+
+/// Path leading out of range of every threat, given as positions with the range to keep from them
+pub fn find_flee_path(from: WorldPos, threats: &[(WorldPos, u32)]) -> Result<Vec<WorldPos>> {
+	let callback = Closure::wrap(Box::new(move |room: JsString| -> JsValue {
+		let room = room.as_string().unwrap();
+		room_costs(&room, MatrixOptions::default())
+			.unwrap_or_default()
+			.to_js()
+			.into()
+	}) as Box<dyn FnMut(JsString) -> JsValue>);
+
+	let goals = threats
+		.iter()
+		.map(|(pos, range)| {
+			let goal = Object::new();
+			set(&goal, "pos", &room_position(*pos));
+			set(&goal, "range", &JsValue::from(*range));
+			goal
+		})
+		.collect::<Array>();
+
+	let options = Object::new();
+	set(&options, "roomCallback", callback.as_ref());
+	set(&options, "flee", &JsValue::TRUE);
+	set(&options, "plainCost", &JsValue::from(2));
+	set(&options, "swampCost", &JsValue::from(10));
+	set(&options, "maxOps", &JsValue::from(MAX_OPS_PER_ROOM));
+	set(&options, "maxRooms", &JsValue::from(2));
+
+	let result = path_finder_search(&room_position(from), &goals, &options);
+	let path = Reflect::get(&result, &JsValue::from("path"))
+		.map_err(|_| Error::NoneFound)?
+		.dyn_into::<Array>()
+		.map_err(|_| Error::NoneFound)?;
+
+	Ok(path
+		.iter()
+		.map(RoomPosition::from)
+		.filter_map(|pos| world_pos(&pos))
+		.collect())
+}
+
+pub fn prune_routes() {
+	let time = Game::time();
+	ROUTES
+		.lock()
+		.unwrap()
+		.retain(|_, route| time.saturating_sub(route.created) < ROUTE_TTL);
+}
@@ -1,18 +1,31 @@
 use wasm_bindgen::prelude::*;
 
-use js_sys::{JsString, Object, JSON::stringify};
+use js_sys::{Array, JsString, Object, Reflect, JSON::stringify};
 
 use web_sys::console;
 
+use serde::Serialize;
+
+use num_traits::cast::FromPrimitive;
+
 use screeps::{
-	objects::{Creep, StructureSpawn},
-	Game,
+	constants::{Find, ReturnCode},
+	objects::{
+		Creep, Room, RoomObject, RoomPosition, Structure, StructureController, StructureLink,
+		StructureSpawn,
+	},
+	Game, Mineral,
 };
 
 use crate::{
 	builds::CreepBuild,
+	codec::{self, Encoding},
+	config,
+	coords::{RoomCoord, WorldPos},
 	error::{Error, Result},
 	jobs::CreepJob,
+	log_warn,
+	logging::Context,
 	structures::{CreepOptions, CreepState},
 };
 
@@ -23,37 +36,262 @@ pub fn log_pretty(x: impl Into<Object>) {
 	console::log_1(&stringify(&o).unwrap());
 }
 
-pub fn copy_state_in(creep: Creep) -> Result<(Creep, CreepState)> {
-	let state = creep
-		.memory()
-		.clone()
-		.into_serde::<CreepState>()
-		.map_err(|_| {
-			let name = creep.name().as_string().unwrap();
-			let pos = creep.pos().unwrap();
-			Error::Deserialize(name, pos.x(), pos.y())
-		})?;
-	Ok((creep, state))
+pub fn room_name(room: &Room) -> String { room.name().as_string().unwrap() }
+
+/// Look up a room by name, `None` if we do not have vision of it
+pub fn visible_room(name: &str) -> Option<Room> {
+	Reflect::get(&Game::rooms(), &JsValue::from(name))
+		.ok()
+		.filter(|room| !room.is_undefined())
+		.map(Room::from)
+}
+
+fn memory_root() -> Object {
+	Reflect::get(&js_sys::global(), &JsValue::from("Memory"))
+		.unwrap()
+		.into()
+}
+
+/// Top level entry of `Memory`, `None` if unset
+pub fn memory_get(key: &str) -> Option<JsValue> {
+	Reflect::get(&memory_root(), &JsValue::from(key))
+		.ok()
+		.filter(|value| !value.is_undefined())
+}
+
+pub fn memory_set(key: &str, value: &impl Serialize) -> Result<()> {
+	Reflect::set(
+		&memory_root(),
+		&JsValue::from(key),
+		&JsValue::from_serde(value)?,
+	)
+	.unwrap();
+	Ok(())
+}
+
+/// Read a creep's state from its memory. Memory which cannot be read, such as binary state
+/// written by an older version, is reported and replaced by a fresh state, so one creep cannot
+/// stop the others from running.
+pub fn copy_state_in(creep: Creep) -> (Creep, CreepState) {
+	let memory = creep.memory();
+	let state = match memory.as_string() {
+		Some(packed) => codec::from_packed::<CreepState>(&packed),
+		None => memory.into_serde::<CreepState>().map_err(Error::from),
+	};
+	let mut state = state.unwrap_or_else(|e| {
+		let name = creep.name().as_string().unwrap();
+		let room = creep.room().as_ref().map(room_name);
+		log_warn!(ctx: Context::creep(name.as_str(), room), "resetting unreadable state: {}", e);
+		// Names start with the build, see `spawn_creep`
+		let build = name
+			.split(':')
+			.next()
+			.and_then(|build| build.parse().ok())
+			.unwrap_or(CreepBuild::Worker1_1);
+		CreepState::new(build, CreepJob::None, String::new())
+	});
+	if state.home.is_empty() {
+		// Creeps spawned before colonies existed adopt the room they are standing in
+		state.home = creep.room().as_ref().map(room_name).unwrap_or_default();
+	}
+	(creep, state)
 }
 
 pub fn copy_state_out(creep: &Creep, state: CreepState) -> Result<()> {
-	creep.set_memory(&JsValue::from_serde(&state)?);
+	let memory = match config::with(|config| config.encoding.creeps) {
+		Encoding::Json => JsValue::from_serde(&state)?,
+		Encoding::Binary => JsValue::from(codec::to_packed(&state)?),
+	};
+	creep.set_memory(&memory);
 
 	Ok(())
 }
 
-pub fn spawn_creep(spawner: &StructureSpawn, build: &CreepBuild) {
+pub fn spawn_creep(spawner: &StructureSpawn, build: &CreepBuild) -> ReturnCode {
 	let spawner_name = spawner.name().as_string().unwrap();
 	let name = JsString::from(format!("{}:{}:{}", build, spawner_name, Game::time()));
 
-	let creep = CreepState::new(build.clone(), CreepJob::None);
+	let home = room_name(&spawner.room().unwrap());
+	let creep = CreepState::new(build.clone(), CreepJob::None, home);
 	let creep_options = CreepOptions {
 		memory: Some(creep.clone()),
 	};
 
-	spawner.spawn_creep(
+	ReturnCode::from_i8(spawner.spawn_creep(
 		&creep.build.parts().to_array(),
 		&name,
 		Some(Object::from(JsValue::from_serde(&creep_options).unwrap())),
-	);
+	))
+	.unwrap()
+}
+
+/// Extend a creep's life at the cost of some energy, which also removes its boosts
+pub fn renew_creep(spawner: &StructureSpawn, creep: &Creep) -> ReturnCode {
+	ReturnCode::from_i8(spawner.renew_creep_raw(creep)).unwrap()
+}
+
+/// Kill a creep next to the spawn, refunding part of its cost
+pub fn recycle_creep(spawner: &StructureSpawn, creep: &Creep) -> ReturnCode {
+	ReturnCode::from_i8(spawner.recycle_creep_raw(creep)).unwrap()
+}
+
+/// Pick up a resource lying on the ground
+pub fn pickup(creep: &Creep, target: &RoomObject) -> ReturnCode {
+	ReturnCode::from_i8(creep.pickup_raw(target)).unwrap()
+}
+
+pub fn repair(creep: &Creep, target: &Structure) -> ReturnCode {
+	ReturnCode::from_i8(creep.repair_raw(target)).unwrap()
+}
+
+/// Hand over a resource given by name, which covers minerals the typed API has no constant for
+pub fn transfer_resource(
+	creep: &Creep,
+	target: &RoomObject,
+	resource: &str,
+	amount: Option<u32>,
+) -> ReturnCode {
+	ReturnCode::from_i8(creep.transfer_raw(target, resource, amount)).unwrap()
+}
+
+/// Take a resource given by name, which covers minerals the typed API has no constant for
+pub fn withdraw_resource(
+	creep: &Creep,
+	target: &RoomObject,
+	resource: &str,
+	amount: Option<u32>,
+) -> ReturnCode {
+	ReturnCode::from_i8(creep.withdraw_raw(target, resource, amount)).unwrap()
+}
+
+pub fn transfer_energy(
+	link: &StructureLink,
+	target: &StructureLink,
+	amount: Option<u32>,
+) -> ReturnCode {
+	ReturnCode::from_i8(link.transfer_energy_raw(target, amount)).unwrap()
+}
+
+/// Amount of a resource held by a structure, tombstone or ruin
+pub fn stored(object: &RoomObject, resource: &str) -> u32 {
+	object
+		.any_store_raw()
+		.used_capacity_raw(resource)
+		.unwrap_or(0)
+}
+
+/// Id of any room object, which the typed API only offers per object type
+pub fn object_id(object: &RoomObject) -> JsString { object.object_id_raw() }
+
+/// Resource type and amount of a dropped resource
+pub fn dropped_resource(object: &RoomObject) -> (String, u32) {
+	(object.dropped_type_raw(), object.dropped_amount_raw())
+}
+
+pub fn hits(structure: &Structure) -> u32 { structure.hits_raw() }
+
+pub fn hits_max(structure: &Structure) -> u32 { structure.hits_max_raw() }
+
+/// Ticks before a structure such as a link or an extractor can be used again
+pub fn cooldown(structure: &impl AsRef<JsValue>) -> u32 {
+	structure
+		.as_ref()
+		.unchecked_ref::<Structure>()
+		.cooldown_raw()
+}
+
+/// Amount left in a mineral before it regenerates
+pub fn mineral_amount(mineral: &Mineral) -> u32 { mineral.mineral_amount_raw() }
+
+/// Look up one of our creeps by name
+pub fn find_creep(name: &str) -> Option<Creep> {
+	Reflect::get(&Game::creeps(), &JsValue::from(name))
+		.ok()
+		.filter(|creep| !creep.is_undefined())
+		.map(Creep::from)
+}
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(method, js_name = find)]
+	fn find_raw(this: &Room, ty: i32) -> Array;
+
+	#[wasm_bindgen(method, getter = ticksToLive)]
+	fn ticks_to_live_raw(this: &Creep) -> Option<u32>;
+
+	#[wasm_bindgen(method, getter = ticksToDowngrade)]
+	fn ticks_to_downgrade_raw(this: &StructureController) -> Option<u32>;
+
+	#[wasm_bindgen(method, js_name = renewCreep)]
+	fn renew_creep_raw(this: &StructureSpawn, target: &Creep) -> i8;
+
+	#[wasm_bindgen(method, js_name = recycleCreep)]
+	fn recycle_creep_raw(this: &StructureSpawn, target: &Creep) -> i8;
+
+	#[wasm_bindgen(method, js_name = pickup)]
+	fn pickup_raw(this: &Creep, target: &RoomObject) -> i8;
+
+	#[wasm_bindgen(method, js_name = repair)]
+	fn repair_raw(this: &Creep, target: &Structure) -> i8;
+
+	#[wasm_bindgen(method, js_name = transfer)]
+	fn transfer_raw(this: &Creep, target: &RoomObject, resource: &str, amount: Option<u32>) -> i8;
+
+	#[wasm_bindgen(method, js_name = withdraw)]
+	fn withdraw_raw(this: &Creep, target: &RoomObject, resource: &str, amount: Option<u32>) -> i8;
+
+	#[wasm_bindgen(method, js_name = transferEnergy)]
+	fn transfer_energy_raw(this: &StructureLink, target: &StructureLink, amount: Option<u32>)
+		-> i8;
+
+	/// Store of a structure, tombstone or ruin, read without knowing which it is
+	type AnyStore;
+
+	#[wasm_bindgen(method, getter = store)]
+	fn any_store_raw(this: &RoomObject) -> AnyStore;
+
+	#[wasm_bindgen(method, js_name = getUsedCapacity)]
+	fn used_capacity_raw(this: &AnyStore, resource: &str) -> Option<u32>;
+
+	#[wasm_bindgen(method, getter = id)]
+	fn object_id_raw(this: &RoomObject) -> JsString;
+
+	#[wasm_bindgen(method, getter = amount)]
+	fn dropped_amount_raw(this: &RoomObject) -> u32;
+
+	#[wasm_bindgen(method, getter = resourceType)]
+	fn dropped_type_raw(this: &RoomObject) -> String;
+
+	#[wasm_bindgen(method, getter = hits)]
+	fn hits_raw(this: &Structure) -> u32;
+
+	#[wasm_bindgen(method, getter = hitsMax)]
+	fn hits_max_raw(this: &Structure) -> u32;
+
+	#[wasm_bindgen(method, getter = cooldown)]
+	fn cooldown_raw(this: &Structure) -> u32;
+
+	#[wasm_bindgen(method, getter = mineralAmount)]
+	fn mineral_amount_raw(this: &Mineral) -> u32;
+}
+
+pub fn room_find(room: &Room, find: Find) -> Array { room.find_raw(find as i32) }
+
+/// Ticks before a creep dies, `None` while it is still spawning
+pub fn ticks_to_live(creep: &Creep) -> Option<u32> { creep.ticks_to_live_raw() }
+
+/// Ticks before an owned controller loses a level, `None` if the controller is not owned
+pub fn ticks_to_downgrade(controller: &StructureController) -> Option<u32> {
+	controller.ticks_to_downgrade_raw()
+}
+
+/// World position of a room position, `None` in rooms outside the world grid like `sim`
+pub fn world_pos(pos: &RoomPosition) -> Option<WorldPos> {
+	let room = RoomCoord::parse(&pos.room_name().as_string()?)?;
+	Some(WorldPos::from_local(room, pos.x(), pos.y()))
+}
+
+pub fn room_position(pos: WorldPos) -> RoomPosition {
+	let (x, y) = pos.local();
+	RoomPosition::new(x, y, &JsString::from(pos.room().to_string()))
 }
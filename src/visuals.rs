@@ -0,0 +1,344 @@
+use wasm_bindgen::prelude::*;
+
+use serde::{Deserialize, Serialize};
+
+use screeps::{
+	constants::{Find, Part},
+	objects::{ConstructionSite, Creep, Room, RoomObject},
+	Source,
+};
+
+use std::collections::HashMap;
+
+use crate::{
+	config,
+	cost_matrix::{room_costs, MatrixOptions, IMPASSABLE},
+	error::Result,
+	jobs::CreepJob,
+	structures::{CreepState, CreepTarget},
+	util::{room_find, visible_room},
+};
+
+#[wasm_bindgen]
+extern "C" {
+	type RoomVisual;
+
+	#[wasm_bindgen(constructor)]
+	fn new(room: &str) -> RoomVisual;
+
+	#[wasm_bindgen(method)]
+	fn line(this: &RoomVisual, x1: f64, y1: f64, x2: f64, y2: f64, style: &JsValue);
+
+	#[wasm_bindgen(method)]
+	fn circle(this: &RoomVisual, x: f64, y: f64, style: &JsValue);
+
+	#[wasm_bindgen(method)]
+	fn rect(this: &RoomVisual, x: f64, y: f64, width: f64, height: f64, style: &JsValue);
+
+	#[wasm_bindgen(method)]
+	fn text(this: &RoomVisual, text: &str, x: f64, y: f64, style: &JsValue);
+}
+
+/// Overlays which can be drawn in a room
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Layers {
+	pub jobs: bool,
+	pub sources: bool,
+	pub planned: bool,
+	pub cost_matrix: bool,
+	pub threats: bool,
+}
+
+impl Default for Layers {
+	fn default() -> Self {
+		Layers {
+			jobs: true,
+			sources: true,
+			planned: true,
+			cost_matrix: false,
+			threats: true,
+		}
+	}
+}
+
+impl Layers {
+	/// Every layer turned off, for rooms which only want the layers asked for
+	pub fn none() -> Self {
+		Layers {
+			jobs: false,
+			sources: false,
+			planned: false,
+			cost_matrix: false,
+			threats: false,
+		}
+	}
+
+	/// Toggle a layer by name, returning false if there is no such layer
+	pub fn set(&mut self, layer: &str, enabled: bool) -> bool {
+		let flag = match layer {
+			"jobs" => &mut self.jobs,
+			"sources" => &mut self.sources,
+			"planned" => &mut self.planned,
+			"costs" => &mut self.cost_matrix,
+			"threats" => &mut self.threats,
+			_ => return false,
+		};
+		*flag = enabled;
+		true
+	}
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VisualsConfig {
+	/// Rooms overlays are drawn in, with the layers shown in each
+	pub rooms: HashMap<String, Layers>,
+}
+
+#[derive(Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Style {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	color: Option<&'static str>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	fill: Option<&'static str>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	stroke: Option<&'static str>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	radius: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	opacity: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	font: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	line_style: Option<&'static str>,
+}
+
+impl Style {
+	fn js(&self) -> JsValue { JsValue::from_serde(self).unwrap() }
+}
+
+fn draw_jobs(visual: &RoomVisual, room: &str, creep_pairs: &[(Creep, CreepState)]) {
+	for (creep, state) in creep_pairs {
+		let pos = match creep.pos() {
+			Some(pos) if pos.room_name().as_string().as_deref() == Some(room) => pos,
+			_ => continue,
+		};
+		let (x, y) = (pos.x() as f64, pos.y() as f64);
+		let colour = state.job.kind().colour();
+
+		visual.text(
+			&state.job.label(),
+			x,
+			y - 0.6,
+			&Style {
+				color: Some(colour),
+				font: Some(0.3),
+				..Default::default()
+			}
+			.js(),
+		);
+
+		let target = state
+			.job
+			.target()
+			.and_then(|target| target.to_value().ok())
+			.map(RoomObject::from)
+			.and_then(|target| target.pos())
+			.filter(|target| target.room_name().as_string().as_deref() == Some(room));
+		if let Some(target) = target {
+			visual.line(
+				x,
+				y,
+				target.x() as f64,
+				target.y() as f64,
+				&Style {
+					color: Some(colour),
+					opacity: Some(0.5),
+					line_style: Some("dashed"),
+					..Default::default()
+				}
+				.js(),
+			);
+		}
+	}
+}
+
+fn draw_sources(visual: &RoomVisual, room: &Room, creep_pairs: &[(Creep, CreepState)]) {
+	let mut assigned = HashMap::<&CreepTarget, u32>::new();
+	creep_pairs
+		.iter()
+		.filter(|(_, state)| matches!(state.job, CreepJob::Harvest(_)))
+		.filter_map(|(_, state)| state.job.target())
+		.for_each(|target| *assigned.entry(target).or_insert(0) += 1);
+
+	room_find(room, Find::Sources)
+		.iter()
+		.map(Source::from)
+		.for_each(|source| {
+			let pos = match source.pos() {
+				Some(pos) => pos,
+				None => return,
+			};
+			let target = CreepTarget::from_id(&source.id());
+			let count = assigned.get(&target).copied().unwrap_or(0);
+			visual.circle(
+				pos.x() as f64,
+				pos.y() as f64,
+				&Style {
+					radius: Some(0.7),
+					fill: Some("transparent"),
+					stroke: Some("#ffe56d"),
+					..Default::default()
+				}
+				.js(),
+			);
+			visual.text(
+				&format!("{}", count),
+				pos.x() as f64 + 0.8,
+				pos.y() as f64 + 0.2,
+				&Style {
+					color: Some("#ffe56d"),
+					font: Some(0.5),
+					..Default::default()
+				}
+				.js(),
+			);
+		});
+}
+
+fn draw_planned(visual: &RoomVisual, room: &Room) {
+	room_find(room, Find::MyConstructionSites)
+		.iter()
+		.map(ConstructionSite::from)
+		.for_each(|site| {
+			let pos = match site.pos() {
+				Some(pos) => pos,
+				None => return,
+			};
+			let (x, y) = (pos.x() as f64, pos.y() as f64);
+			visual.rect(
+				x - 0.4,
+				y - 0.4,
+				0.8,
+				0.8,
+				&Style {
+					fill: Some("transparent"),
+					stroke: Some("#7dff7d"),
+					opacity: Some(0.6),
+					..Default::default()
+				}
+				.js(),
+			);
+			let name = format!("{:?}", site.structure_type());
+			visual.text(
+				&name[..name.len().min(3)],
+				x,
+				y + 0.15,
+				&Style {
+					color: Some("#7dff7d"),
+					font: Some(0.3),
+					..Default::default()
+				}
+				.js(),
+			);
+		});
+}
+
+fn draw_cost_matrix(visual: &RoomVisual, room: &str) {
+	let costs = match room_costs(room, MatrixOptions::default()) {
+		Some(costs) => costs,
+		None => return,
+	};
+	for y in 0..50u8 {
+		for x in 0..50u8 {
+			let cost = costs.get(x, y);
+			if cost == 0 {
+				continue;
+			}
+			let colour = if cost == IMPASSABLE {
+				"#ff5050"
+			}
+			else {
+				"#cccccc"
+			};
+			visual.text(
+				&cost.to_string(),
+				x as f64,
+				y as f64 + 0.15,
+				&Style {
+					color: Some(colour),
+					font: Some(0.3),
+					opacity: Some(0.6),
+					..Default::default()
+				}
+				.js(),
+			);
+		}
+	}
+}
+
+fn draw_threats(visual: &RoomVisual, room: &Room) {
+	room_find(room, Find::HostileCreeps)
+		.iter()
+		.map(Creep::from)
+		.for_each(|hostile| {
+			let range = if hostile.get_active_bodyparts(Part::RangedAttack) > 0 {
+				3.0
+			}
+			else if hostile.get_active_bodyparts(Part::Attack) > 0 {
+				1.0
+			}
+			else {
+				return;
+			};
+			let pos = match hostile.pos() {
+				Some(pos) => pos,
+				None => return,
+			};
+			visual.rect(
+				pos.x() as f64 - range - 0.5,
+				pos.y() as f64 - range - 0.5,
+				range * 2.0 + 1.0,
+				range * 2.0 + 1.0,
+				&Style {
+					fill: Some("#ff5050"),
+					opacity: Some(0.15),
+					..Default::default()
+				}
+				.js(),
+			);
+		});
+}
+
+/// Draw the enabled overlays in every room they are toggled on for
+pub fn draw(creep_pairs: &[(Creep, CreepState)]) -> Result<()> {
+	let rooms = config::with(|config| config.visuals.rooms.clone());
+
+	for (name, layers) in &rooms {
+		let room = match visible_room(name) {
+			Some(room) => room,
+			None => continue,
+		};
+		let visual = RoomVisual::new(name);
+
+		if layers.cost_matrix {
+			draw_cost_matrix(&visual, name);
+		}
+		if layers.threats {
+			draw_threats(&visual, &room);
+		}
+		if layers.planned {
+			draw_planned(&visual, &room);
+		}
+		if layers.sources {
+			draw_sources(&visual, &room, creep_pairs);
+		}
+		if layers.jobs {
+			draw_jobs(&visual, name, creep_pairs);
+		}
+	}
+
+	Ok(())
+}